@@ -0,0 +1,304 @@
+use std::fmt;
+
+use bech32::segwit;
+
+use crate::encoding::{txid_to_bytes, EncodingError};
+use crate::merkle::merkle_root;
+use crate::script::{assemble, disassemble};
+use crate::transaction_loader::{calculate_transaction_fee, Prevout, Transaction, Vin, Vout};
+use crate::transaction_validator::double_sha256;
+
+/// Satoshis per bitcoin.
+pub const COIN: u64 = 100_000_000;
+
+/// Blocks between subsidy halvings.
+pub const HALVING_INTERVAL: u64 = 210_000;
+
+/// Default payout address (the genesis block's), used when `COINBASE_ADDRESS` is unset.
+pub const DEFAULT_PAYOUT_ADDRESS: &str = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa";
+
+/// BIP141 witness reserved value, carried as the coinbase input's only witness item.
+pub const WITNESS_RESERVED_VALUE: [u8; 32] = [0; 32];
+
+/// Start of the witness commitment script: OP_RETURN, a 36-byte push, then the 0xaa21a9ed tag.
+pub const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+/// Reasons a payout address could not be turned into a scriptPubKey.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    /// Neither valid base58check nor a valid bech32/bech32m segwit address.
+    Unrecognized(String),
+    /// Base58check decoded, but the version byte is not a known P2PKH/P2SH prefix.
+    UnknownVersion(u8),
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressError::Unrecognized(address) => write!(f, "{:?} is not a valid address", address),
+            AddressError::UnknownVersion(version) => write!(f, "unknown base58 version byte 0x{:02x}", version),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+/// An address the coinbase pays to, resolved to its output script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutAddress {
+    pub address: String,
+    pub scriptpubkey: Vec<u8>,
+    pub scriptpubkey_asm: String,
+    pub scriptpubkey_type: &'static str,
+}
+
+impl PayoutAddress {
+    /// Accepts P2PKH/P2SH base58check addresses and segwit v0/v1+ bech32(m) addresses,
+    /// for mainnet, testnet and regtest.
+    pub fn parse(address: &str) -> Result<PayoutAddress, AddressError> {
+        if let Ok((_hrp, version, program)) = segwit::decode(address) {
+            return Ok(PayoutAddress::witness(address, version.to_u8(), &program));
+        }
+        let data = bs58::decode(address)
+            .into_vec()
+            .map_err(|_| AddressError::Unrecognized(address.to_string()))?;
+        if data.len() != 25 || double_sha256(&data[..21])[..4] != data[21..] {
+            return Err(AddressError::Unrecognized(address.to_string()));
+        }
+        let hash = hex::encode(&data[1..21]);
+        let (asm, scriptpubkey_type) = match data[0] {
+            0x00 | 0x6f => (format!("OP_DUP OP_HASH160 {} OP_EQUALVERIFY OP_CHECKSIG", hash), "p2pkh"),
+            0x05 | 0xc4 => (format!("OP_HASH160 {} OP_EQUAL", hash), "p2sh"),
+            version => return Err(AddressError::UnknownVersion(version)),
+        };
+        let scriptpubkey = assemble(&asm).expect("template asm assembles");
+        Ok(PayoutAddress {
+            address: address.to_string(),
+            scriptpubkey_asm: disassemble(&scriptpubkey),
+            scriptpubkey,
+            scriptpubkey_type,
+        })
+    }
+
+    fn witness(address: &str, version: u8, program: &[u8]) -> PayoutAddress {
+        let scriptpubkey_type = match (version, program.len()) {
+            (0, 20) => "v0_p2wpkh",
+            (0, 32) => "v0_p2wsh",
+            (1, 32) => "v1_p2tr",
+            _ => "unknown",
+        };
+        let mut scriptpubkey = vec![if version == 0 { 0x00 } else { 0x50 + version }, program.len() as u8];
+        scriptpubkey.extend_from_slice(program);
+        PayoutAddress {
+            address: address.to_string(),
+            scriptpubkey_asm: disassemble(&scriptpubkey),
+            scriptpubkey,
+            scriptpubkey_type,
+        }
+    }
+
+    /// An output paying `value` satoshis to this address.
+    pub fn to_vout(&self, value: u64) -> Vout {
+        Vout {
+            scriptpubkey: hex::encode(&self.scriptpubkey),
+            scriptpubkey_asm: self.scriptpubkey_asm.clone(),
+            scriptpubkey_type: self.scriptpubkey_type.to_string(),
+            scriptpubkey_address: self.address.clone(),
+            value: value as i64,
+        }
+    }
+}
+
+/// New coins created by a block at `height`: 50 BTC, halved every 210,000 blocks.
+pub fn block_subsidy(height: u64) -> u64 {
+    let halvings = height / HALVING_INTERVAL;
+    if halvings >= 64 {
+        return 0;
+    }
+    (50 * COIN) >> halvings
+}
+
+/// Sum of the fees paid by `transactions`.
+pub fn total_fees(transactions: &[Transaction]) -> u64 {
+    transactions
+        .iter()
+        .map(|transaction| calculate_transaction_fee(transaction).max(0) as u64)
+        .sum()
+}
+
+/// The height push BIP34 requires at the start of the coinbase scriptSig,
+/// as Bitcoin Core's `CScript() << height` writes it, with its asm.
+pub fn bip34_height_push(height: u64) -> (Vec<u8>, String) {
+    match height {
+        0 => (vec![0x00], "OP_0".to_string()),
+        1..=16 => (vec![0x50 + height as u8], format!("OP_PUSHNUM_{}", height)),
+        _ => {
+            // Minimal little-endian script number, with a sign byte if the top bit is set
+            let mut number = height.to_le_bytes().to_vec();
+            while number.last() == Some(&0) {
+                number.pop();
+            }
+            if number.last().is_some_and(|byte| byte & 0x80 != 0) {
+                number.push(0x00);
+            }
+            let asm = format!("OP_PUSHBYTES_{} {}", number.len(), hex::encode(&number));
+            let mut push = vec![number.len() as u8];
+            push.extend_from_slice(&number);
+            (push, asm)
+        }
+    }
+}
+
+/// Builds the coinbase for a block at `height` paying subsidy plus `fees` to `payout`.
+///
+/// The scriptSig is the BIP34 height followed by `OP_0`, which keeps it within the
+/// 2-100 byte range consensus requires even for heights encoded as a single opcode.
+pub fn build_coinbase(height: u64, fees: u64, payout: &PayoutAddress) -> Transaction {
+    let (mut scriptsig, height_asm) = bip34_height_push(height);
+    scriptsig.push(0x00);
+
+    Transaction {
+        version: 2,
+        locktime: 0,
+        vin: vec![Vin {
+            txid: "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            vout: u32::MAX,
+            prevout: Prevout::default(),
+            scriptsig: hex::encode(&scriptsig),
+            scriptsig_asm: format!("{} OP_0", height_asm),
+            witness: None,
+            sequence: u32::MAX,
+            is_coinbase: true,
+            inner_redeemscript_asm: String::new(),
+            inner_witnessscript_asm: String::new(),
+        }],
+        vout: vec![payout.to_vout(block_subsidy(height) + fees)],
+        is_coinbase: true,
+    }
+}
+
+/// The BIP141 commitment for a block whose non-coinbase transactions are `transactions`:
+/// double SHA-256 of the wtxid Merkle root (coinbase wtxid taken as zero) and the reserved value.
+pub fn witness_commitment(transactions: &[Transaction]) -> Result<[u8; 32], EncodingError> {
+    let mut wtxids = vec![[0u8; 32]];
+    for transaction in transactions {
+        wtxids.push(txid_to_bytes(&transaction.wtxid()?)?);
+    }
+    let root = merkle_root(&wtxids).expect("the coinbase is always present");
+    Ok(double_sha256(&[root, WITNESS_RESERVED_VALUE].concat()))
+}
+
+/// Adds the witness commitment output and reserved-value witness to `coinbase`
+/// when any of `transactions` carries witness data, as BIP141 then requires.
+pub fn add_witness_commitment(coinbase: &mut Transaction, transactions: &[Transaction]) -> Result<(), EncodingError> {
+    if !transactions.iter().any(Transaction::has_witness) {
+        return Ok(());
+    }
+    let commitment = witness_commitment(transactions)?;
+    let mut scriptpubkey = WITNESS_COMMITMENT_HEADER.to_vec();
+    scriptpubkey.extend_from_slice(&commitment);
+    coinbase.vout.push(Vout {
+        scriptpubkey: hex::encode(&scriptpubkey),
+        scriptpubkey_asm: disassemble(&scriptpubkey),
+        scriptpubkey_type: "op_return".to_string(),
+        scriptpubkey_address: String::new(),
+        value: 0,
+    });
+    coinbase.vin[0].witness = Some(vec![hex::encode(WITNESS_RESERVED_VALUE)]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::{deserialize, serialize};
+    use crate::transaction_loader::load_json_transaction;
+
+    #[test]
+    fn test_block_subsidy() {
+        assert_eq!(block_subsidy(0), 50 * COIN);
+        assert_eq!(block_subsidy(209_999), 50 * COIN);
+        assert_eq!(block_subsidy(210_000), 25 * COIN);
+        assert_eq!(block_subsidy(840_000), 312_500_000);
+        assert_eq!(block_subsidy(64 * HALVING_INTERVAL), 0);
+    }
+
+    #[test]
+    fn test_bip34_height_push() {
+        assert_eq!(bip34_height_push(0).0, vec![0x00]);
+        assert_eq!(bip34_height_push(16).0, vec![0x60]);
+        assert_eq!(bip34_height_push(17).0, vec![0x01, 0x11]);
+        assert_eq!(bip34_height_push(128).0, vec![0x02, 0x80, 0x00]);
+        // Block 227,931, the first block BIP34 applied to on mainnet
+        let (push, asm) = bip34_height_push(227_931);
+        assert_eq!(hex::encode(push), "035b7a03");
+        assert_eq!(asm, "OP_PUSHBYTES_3 5b7a03");
+    }
+
+    #[test]
+    fn test_parse_payout_address() {
+        let p2pkh = PayoutAddress::parse(DEFAULT_PAYOUT_ADDRESS).unwrap();
+        assert_eq!(hex::encode(&p2pkh.scriptpubkey), "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac");
+        assert_eq!(p2pkh.scriptpubkey_type, "p2pkh");
+
+        let p2wpkh = PayoutAddress::parse("bc1qmht77yh2dauwmnq73ea05ykqr26e7veea8spz6").unwrap();
+        assert_eq!(hex::encode(&p2wpkh.scriptpubkey), "0014ddd7ef12ea6f78edcc1e8e7afa12c01ab59f3339");
+        assert_eq!(p2wpkh.scriptpubkey_asm, "OP_0 OP_PUSHBYTES_20 ddd7ef12ea6f78edcc1e8e7afa12c01ab59f3339");
+
+        let p2tr = PayoutAddress::parse("bc1psjhl3qr8jd3mcvcwsjv3cm9pkc8xa2q3cfnz8v77wm0p5qcnzekqz9mr32").unwrap();
+        assert_eq!(
+            hex::encode(&p2tr.scriptpubkey),
+            "512084aff880679363bc330e84991c6ca1b60e6ea811c26623b3de76de1a0313166c"
+        );
+        assert_eq!(p2tr.scriptpubkey_type, "v1_p2tr");
+
+        // Bad checksum
+        assert!(PayoutAddress::parse("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb").is_err());
+        assert!(PayoutAddress::parse("not an address").is_err());
+    }
+
+    #[test]
+    fn test_build_coinbase() {
+        let payout = PayoutAddress::parse(DEFAULT_PAYOUT_ADDRESS).unwrap();
+        let coinbase = build_coinbase(840_000, 12_345, &payout);
+        assert_eq!(coinbase.vout[0].value, 312_512_345);
+        assert_eq!(coinbase.vin[0].scriptsig, "0340d10c00");
+
+        // Round-trips through the codec, which recognises it as a coinbase
+        let bytes = serialize(&coinbase).unwrap();
+        let decoded: Transaction = deserialize(&bytes).unwrap();
+        assert!(decoded.vin[0].is_coinbase);
+        assert_eq!(decoded.vin[0].scriptsig, coinbase.vin[0].scriptsig);
+        assert_eq!(decoded.vout[0].scriptpubkey, coinbase.vout[0].scriptpubkey);
+    }
+
+    #[test]
+    fn test_witness_commitment() {
+        let payout = PayoutAddress::parse(DEFAULT_PAYOUT_ADDRESS).unwrap();
+
+        // No witness data: the coinbase is left alone
+        let legacy = load_json_transaction("0183b7cc0d270638fc8d51be094a76ada65b7f5f2044512ed42437f97fa999ac");
+        assert!(!legacy.has_witness());
+        let mut coinbase = build_coinbase(0, 0, &payout);
+        add_witness_commitment(&mut coinbase, std::slice::from_ref(&legacy)).unwrap();
+        assert_eq!(coinbase.vout.len(), 1);
+        assert!(!coinbase.has_witness());
+
+        let segwit = load_json_transaction("0018c221bca3da35128baabe412a14c95b6864b2e6f7f7a8ffdd8eb0923dec49");
+        assert!(segwit.has_witness());
+        let transactions = vec![legacy, segwit];
+        add_witness_commitment(&mut coinbase, &transactions).unwrap();
+
+        let root = merkle_root(&[
+            [0u8; 32],
+            txid_to_bytes(&transactions[0].wtxid().unwrap()).unwrap(),
+            txid_to_bytes(&transactions[1].wtxid().unwrap()).unwrap(),
+        ])
+        .unwrap();
+        let expected = double_sha256(&[root, [0u8; 32]].concat());
+        assert_eq!(coinbase.vout[1].scriptpubkey, format!("6a24aa21a9ed{}", hex::encode(expected)));
+        assert_eq!(coinbase.vout[1].value, 0);
+        assert_eq!(coinbase.vin[0].witness, Some(vec!["00".repeat(32)]));
+        assert_ne!(coinbase.txid().unwrap(), coinbase.wtxid().unwrap());
+    }
+}
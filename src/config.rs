@@ -0,0 +1,330 @@
+use std::env;
+use std::fmt;
+
+use crate::coinbase::{PayoutAddress, DEFAULT_PAYOUT_ADDRESS};
+use crate::encoding::txid_to_bytes;
+use crate::target::Target;
+use crate::transaction_loader::MEMPOOL_DIR;
+use crate::verifier::UnknownTypePolicy;
+
+/// How transactions are stored in the mempool directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MempoolFormat {
+    /// One esplora-style JSON transaction per file.
+    Json,
+    /// Raw transaction hex, one per line; prevouts come from a separate file.
+    RawHex,
+}
+
+/// How the block hash checked against the target is computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashScheme {
+    /// Double SHA-256 of the 80-byte header, as Bitcoin does.
+    Header,
+    /// The original Merkle-Damgard hash over the JSON block. Not valid Bitcoin proof of work.
+    Legacy,
+}
+
+/// How the mined block is written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Header hex, coinbase hex, then one txid per line.
+    Ids,
+    /// The full serialized block as hex, ready for `submitblock`.
+    Hex,
+    /// The full serialized block as raw bytes.
+    Binary,
+}
+
+/// Run settings, read from environment variables so `cargo run` works unchanged.
+///
+/// - `MEMPOOL_DIR`: directory to load transactions from (default `./mempool`)
+/// - `MEMPOOL_FORMAT`: `json` (default) or `raw`
+/// - `PREVOUTS_FILE`: JSON array of spent outputs, required for `raw`
+/// - `BLOCK_HASH_SCHEME`: `header` (default) or `legacy`
+/// - `BLOCK_BITS`: compact target as 8 hex digits (default `1f00ffff`)
+/// - `BLOCK_HEIGHT`: height of the mined block, committed to by the coinbase (default `0`)
+/// - `PREVIOUS_BLOCK_HASH`: hash of the block to build on, 64 hex digits (default all zeros)
+/// - `COINBASE_ADDRESS`: address the block reward is paid to (default: the genesis address)
+/// - `OUTPUT_FORMAT`: `ids` (default), `hex` or `binary`
+/// - `OUTPUT_FILE`: where the block is written (default `output.txt`)
+/// - `REJECT_ANNEX`: `true` (default) to leave out taproot spends with an annex, as Bitcoin Core's relay policy does, or `false`
+/// - `REPORT_FILE`: where to write a JSON lines report of every mempool entry (default: no report)
+/// - `UNKNOWN_SCRIPT_TYPES`: `reject` (default) or `accept` outputs and prevouts of a type with no registered verifier
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub mempool_dir: String,
+    pub mempool_format: MempoolFormat,
+    pub prevouts_file: Option<String>,
+    pub hash_scheme: HashScheme,
+    pub target: Target,
+    pub height: u64,
+    pub previous_block_hash: String,
+    pub payout_address: PayoutAddress,
+    pub output_format: OutputFormat,
+    pub output_file: String,
+    pub reject_annex: bool,
+    pub report_file: Option<String>,
+    pub unknown_script_types: UnknownTypePolicy,
+}
+
+/// Compact form of the default mining target, 0000ffff00..00.
+pub const DEFAULT_BITS: u32 = 0x1f00ffff;
+
+/// A setting that could not be understood.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub variable: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.variable, self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            mempool_dir: MEMPOOL_DIR.to_string(),
+            mempool_format: MempoolFormat::Json,
+            prevouts_file: None,
+            hash_scheme: HashScheme::Header,
+            target: Target::from_compact(DEFAULT_BITS).expect("default bits are valid"),
+            height: 0,
+            previous_block_hash: "0".repeat(64),
+            payout_address: PayoutAddress::parse(DEFAULT_PAYOUT_ADDRESS).expect("default address is valid"),
+            output_format: OutputFormat::Ids,
+            output_file: "output.txt".to_string(),
+            reject_annex: true,
+            report_file: None,
+            unknown_script_types: UnknownTypePolicy::Reject,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Result<Config, ConfigError> {
+        Config::from_lookup(|name| env::var(name).ok())
+    }
+
+    /// Builds a config from an arbitrary variable lookup (used by tests).
+    pub fn from_lookup<F: Fn(&str) -> Option<String>>(lookup: F) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        if let Some(dir) = lookup("MEMPOOL_DIR") {
+            config.mempool_dir = dir;
+        }
+        if let Some(format) = lookup("MEMPOOL_FORMAT") {
+            config.mempool_format = match format.to_ascii_lowercase().as_str() {
+                "json" => MempoolFormat::Json,
+                "raw" | "hex" => MempoolFormat::RawHex,
+                other => {
+                    return Err(ConfigError {
+                        variable: "MEMPOOL_FORMAT",
+                        message: format!("unknown format {:?}, expected json or raw", other),
+                    })
+                }
+            };
+        }
+        config.prevouts_file = lookup("PREVOUTS_FILE");
+        if let Some(scheme) = lookup("BLOCK_HASH_SCHEME") {
+            config.hash_scheme = match scheme.to_ascii_lowercase().as_str() {
+                "header" => HashScheme::Header,
+                "legacy" => HashScheme::Legacy,
+                other => {
+                    return Err(ConfigError {
+                        variable: "BLOCK_HASH_SCHEME",
+                        message: format!("unknown scheme {:?}, expected header or legacy", other),
+                    })
+                }
+            };
+        }
+        if let Some(bits) = lookup("BLOCK_BITS") {
+            let compact = u32::from_str_radix(bits.trim_start_matches("0x"), 16).map_err(|_| ConfigError {
+                variable: "BLOCK_BITS",
+                message: format!("{:?} is not a 32-bit hex value", bits),
+            })?;
+            config.target = Target::from_compact(compact).map_err(|error| ConfigError {
+                variable: "BLOCK_BITS",
+                message: error.to_string(),
+            })?;
+        }
+        if let Some(height) = lookup("BLOCK_HEIGHT") {
+            config.height = height.parse().map_err(|_| ConfigError {
+                variable: "BLOCK_HEIGHT",
+                message: format!("{:?} is not a block height", height),
+            })?;
+        }
+        if let Some(hash) = lookup("PREVIOUS_BLOCK_HASH") {
+            txid_to_bytes(&hash).map_err(|_| ConfigError {
+                variable: "PREVIOUS_BLOCK_HASH",
+                message: format!("{:?} is not 32 bytes of hex", hash),
+            })?;
+            config.previous_block_hash = hash;
+        }
+        if let Some(address) = lookup("COINBASE_ADDRESS") {
+            config.payout_address = PayoutAddress::parse(&address).map_err(|error| ConfigError {
+                variable: "COINBASE_ADDRESS",
+                message: error.to_string(),
+            })?;
+        }
+        if let Some(format) = lookup("OUTPUT_FORMAT") {
+            config.output_format = match format.to_ascii_lowercase().as_str() {
+                "ids" => OutputFormat::Ids,
+                "hex" => OutputFormat::Hex,
+                "binary" | "bin" => OutputFormat::Binary,
+                other => {
+                    return Err(ConfigError {
+                        variable: "OUTPUT_FORMAT",
+                        message: format!("unknown format {:?}, expected ids, hex or binary", other),
+                    })
+                }
+            };
+        }
+        if let Some(file) = lookup("OUTPUT_FILE") {
+            config.output_file = file;
+        }
+        if let Some(flag) = lookup("REJECT_ANNEX") {
+            config.reject_annex = match flag.to_ascii_lowercase().as_str() {
+                "true" | "1" => true,
+                "false" | "0" => false,
+                other => {
+                    return Err(ConfigError {
+                        variable: "REJECT_ANNEX",
+                        message: format!("{:?} is not a boolean, expected true or false", other),
+                    })
+                }
+            };
+        }
+        config.report_file = lookup("REPORT_FILE");
+        if let Some(policy) = lookup("UNKNOWN_SCRIPT_TYPES") {
+            config.unknown_script_types = match policy.to_ascii_lowercase().as_str() {
+                "reject" => UnknownTypePolicy::Reject,
+                "accept" => UnknownTypePolicy::Accept,
+                other => {
+                    return Err(ConfigError {
+                        variable: "UNKNOWN_SCRIPT_TYPES",
+                        message: format!("unknown policy {:?}, expected reject or accept", other),
+                    })
+                }
+            };
+        }
+        if config.mempool_format == MempoolFormat::RawHex && config.prevouts_file.is_none() {
+            return Err(ConfigError {
+                variable: "PREVOUTS_FILE",
+                message: "required when MEMPOOL_FORMAT=raw".to_string(),
+            });
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config_from(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Config::from_lookup(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_default_config() {
+        assert_eq!(config_from(&[]).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_raw_format_requires_prevouts() {
+        let error = config_from(&[("MEMPOOL_FORMAT", "raw")]).unwrap_err();
+        assert_eq!(error.variable, "PREVOUTS_FILE");
+
+        let config = config_from(&[("MEMPOOL_FORMAT", "raw"), ("PREVOUTS_FILE", "prevouts.json")]).unwrap();
+        assert_eq!(config.mempool_format, MempoolFormat::RawHex);
+        assert_eq!(config.prevouts_file.as_deref(), Some("prevouts.json"));
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        assert!(config_from(&[("MEMPOOL_FORMAT", "xml")]).is_err());
+    }
+
+    #[test]
+    fn test_legacy_hash_scheme() {
+        let config = config_from(&[("BLOCK_HASH_SCHEME", "legacy")]).unwrap();
+        assert_eq!(config.hash_scheme, HashScheme::Legacy);
+        assert!(config_from(&[("BLOCK_HASH_SCHEME", "sha3")]).is_err());
+    }
+
+    #[test]
+    fn test_block_bits() {
+        let config = config_from(&[("BLOCK_BITS", "1d00ffff")]).unwrap();
+        assert_eq!(config.target, Target::MAX_ATTAINABLE);
+        assert_eq!(config.target.to_compact(), 0x1d00ffff);
+        assert_eq!(Config::default().target.to_compact(), DEFAULT_BITS);
+
+        assert!(config_from(&[("BLOCK_BITS", "nothex")]).is_err());
+        let error = config_from(&[("BLOCK_BITS", "04923456")]).unwrap_err();
+        assert_eq!(error.message, "compact target is negative");
+    }
+
+    #[test]
+    fn test_chain_tip() {
+        let hash = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+        let config = config_from(&[("BLOCK_HEIGHT", "840000"), ("PREVIOUS_BLOCK_HASH", hash)]).unwrap();
+        assert_eq!(config.height, 840_000);
+        assert_eq!(config.previous_block_hash, hash);
+
+        assert_eq!(config_from(&[("BLOCK_HEIGHT", "-1")]).unwrap_err().variable, "BLOCK_HEIGHT");
+        assert_eq!(config_from(&[("PREVIOUS_BLOCK_HASH", "00ff")]).unwrap_err().variable, "PREVIOUS_BLOCK_HASH");
+    }
+
+    #[test]
+    fn test_coinbase_address() {
+        let address = "bc1qmht77yh2dauwmnq73ea05ykqr26e7veea8spz6";
+        let config = config_from(&[("COINBASE_ADDRESS", address)]).unwrap();
+        assert_eq!(config.payout_address.address, address);
+        assert_eq!(config.payout_address.scriptpubkey_type, "v0_p2wpkh");
+
+        let error = config_from(&[("COINBASE_ADDRESS", "bc1qinvalid")]).unwrap_err();
+        assert_eq!(error.variable, "COINBASE_ADDRESS");
+    }
+
+    #[test]
+    fn test_output_format() {
+        let config = config_from(&[("OUTPUT_FORMAT", "hex"), ("OUTPUT_FILE", "block.hex")]).unwrap();
+        assert_eq!(config.output_format, OutputFormat::Hex);
+        assert_eq!(config.output_file, "block.hex");
+        assert_eq!(config_from(&[("OUTPUT_FORMAT", "binary")]).unwrap().output_format, OutputFormat::Binary);
+        assert!(config_from(&[("OUTPUT_FORMAT", "json")]).is_err());
+    }
+
+    #[test]
+    fn test_reject_annex() {
+        assert!(Config::default().reject_annex);
+        assert!(!config_from(&[("REJECT_ANNEX", "false")]).unwrap().reject_annex);
+        assert!(config_from(&[("REJECT_ANNEX", "1")]).unwrap().reject_annex);
+        assert!(config_from(&[("REJECT_ANNEX", "maybe")]).is_err());
+    }
+
+    #[test]
+    fn test_report_file() {
+        assert_eq!(Config::default().report_file, None);
+        let config = config_from(&[("REPORT_FILE", "report.jsonl")]).unwrap();
+        assert_eq!(config.report_file.as_deref(), Some("report.jsonl"));
+    }
+
+    #[test]
+    fn test_unknown_script_types() {
+        assert_eq!(Config::default().unknown_script_types, UnknownTypePolicy::Reject);
+        let config = config_from(&[("UNKNOWN_SCRIPT_TYPES", "accept")]).unwrap();
+        assert_eq!(config.unknown_script_types, UnknownTypePolicy::Accept);
+        assert!(config_from(&[("UNKNOWN_SCRIPT_TYPES", "ignore")]).is_err());
+    }
+}
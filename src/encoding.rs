@@ -0,0 +1,463 @@
+use std::fmt;
+
+use crate::script::disassemble;
+use crate::script_type::esplora_type;
+use crate::transaction_validator::script_address;
+use crate::transaction_loader::{Prevout, Transaction, Vin, Vout};
+
+/// Largest length prefix accepted while decoding (mirrors Bitcoin Core's MAX_SIZE).
+const MAX_COMPACT_SIZE: u64 = 0x0200_0000;
+
+/// Largest amount in satoshis an output may carry: 21 million BTC (Bitcoin Core's MAX_MONEY).
+pub const MAX_MONEY: i64 = 21_000_000 * 100_000_000;
+
+/// Errors raised while converting between `Transaction` values and consensus bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodingError {
+    /// A hex field of the transaction could not be decoded.
+    InvalidHex { field: &'static str, value: String },
+    /// A txid was not exactly 32 bytes long.
+    InvalidTxidLength(usize),
+    /// The input ended before the value was complete.
+    UnexpectedEof,
+    /// A CompactSize was not encoded in its shortest form.
+    NonMinimalCompactSize,
+    /// A CompactSize exceeded the maximum allowed length.
+    OversizedCompactSize(u64),
+    /// Bytes were left over after decoding a complete value.
+    TrailingData(usize),
+    /// The segwit flag byte was not 0x01.
+    UnsupportedSegwitFlag(u8),
+    /// The segwit marker was present but every witness was empty.
+    SuperfluousWitness,
+    /// An output amount above MAX_MONEY.
+    AmountOutOfRange(u64),
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingError::InvalidHex { field, value } => write!(f, "invalid hex in {}: {:?}", field, value),
+            EncodingError::InvalidTxidLength(len) => write!(f, "txid must be 32 bytes, got {}", len),
+            EncodingError::UnexpectedEof => write!(f, "unexpected end of data"),
+            EncodingError::NonMinimalCompactSize => write!(f, "non-minimal CompactSize encoding"),
+            EncodingError::OversizedCompactSize(size) => write!(f, "CompactSize {} exceeds maximum", size),
+            EncodingError::TrailingData(len) => write!(f, "{} trailing bytes after value", len),
+            EncodingError::UnsupportedSegwitFlag(flag) => write!(f, "unsupported segwit flag {:#04x}", flag),
+            EncodingError::SuperfluousWitness => write!(f, "segwit marker present without witness data"),
+            EncodingError::AmountOutOfRange(value) => write!(f, "output amount {} exceeds {}", value, MAX_MONEY),
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+/// Types that can be written in Bitcoin's consensus wire format.
+pub trait Encodable {
+    /// Appends the encoding of `self` to `writer` and returns the number of bytes written.
+    fn consensus_encode(&self, writer: &mut Vec<u8>) -> Result<usize, EncodingError>;
+}
+
+/// Types that can be read from Bitcoin's consensus wire format.
+pub trait Decodable: Sized {
+    /// Reads a value from the front of `reader`, advancing it past the consumed bytes.
+    fn consensus_decode(reader: &mut &[u8]) -> Result<Self, EncodingError>;
+}
+
+/// Serializes a value into a fresh byte vector.
+pub fn serialize<T: Encodable>(value: &T) -> Result<Vec<u8>, EncodingError> {
+    let mut bytes = Vec::new();
+    value.consensus_encode(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Deserializes a value, requiring that every byte of `bytes` is consumed.
+pub fn deserialize<T: Decodable>(bytes: &[u8]) -> Result<T, EncodingError> {
+    let mut reader = bytes;
+    let value = T::consensus_decode(&mut reader)?;
+    if !reader.is_empty() {
+        return Err(EncodingError::TrailingData(reader.len()));
+    }
+    Ok(value)
+}
+
+/// A CompactSize unsigned integer, used for counts and script lengths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactSize(pub u64);
+
+impl Encodable for CompactSize {
+    fn consensus_encode(&self, writer: &mut Vec<u8>) -> Result<usize, EncodingError> {
+        match self.0 {
+            0..=0xfc => {
+                writer.push(self.0 as u8);
+                Ok(1)
+            }
+            0xfd..=0xffff => {
+                writer.push(0xfd);
+                writer.extend_from_slice(&(self.0 as u16).to_le_bytes());
+                Ok(3)
+            }
+            0x1_0000..=0xffff_ffff => {
+                writer.push(0xfe);
+                writer.extend_from_slice(&(self.0 as u32).to_le_bytes());
+                Ok(5)
+            }
+            _ => {
+                writer.push(0xff);
+                writer.extend_from_slice(&self.0.to_le_bytes());
+                Ok(9)
+            }
+        }
+    }
+}
+
+impl Decodable for CompactSize {
+    fn consensus_decode(reader: &mut &[u8]) -> Result<Self, EncodingError> {
+        let (value, minimum) = match u8::consensus_decode(reader)? {
+            0xfd => (u16::consensus_decode(reader)? as u64, 0xfd),
+            0xfe => (u32::consensus_decode(reader)? as u64, 0x1_0000),
+            0xff => (u64::consensus_decode(reader)?, 0x1_0000_0000),
+            byte => (byte as u64, 0),
+        };
+        if value < minimum {
+            return Err(EncodingError::NonMinimalCompactSize);
+        }
+        if value > MAX_COMPACT_SIZE {
+            return Err(EncodingError::OversizedCompactSize(value));
+        }
+        Ok(CompactSize(value))
+    }
+}
+
+fn take<'a>(reader: &mut &'a [u8], len: usize) -> Result<&'a [u8], EncodingError> {
+    if reader.len() < len {
+        return Err(EncodingError::UnexpectedEof);
+    }
+    let (head, tail) = reader.split_at(len);
+    *reader = tail;
+    Ok(head)
+}
+
+macro_rules! impl_int_encodable {
+    ($ty:ty) => {
+        impl Encodable for $ty {
+            fn consensus_encode(&self, writer: &mut Vec<u8>) -> Result<usize, EncodingError> {
+                let bytes = self.to_le_bytes();
+                writer.extend_from_slice(&bytes);
+                Ok(bytes.len())
+            }
+        }
+
+        impl Decodable for $ty {
+            fn consensus_decode(reader: &mut &[u8]) -> Result<Self, EncodingError> {
+                let bytes = take(reader, std::mem::size_of::<$ty>())?;
+                Ok(<$ty>::from_le_bytes(bytes.try_into().expect("slice has the integer's size")))
+            }
+        }
+    };
+}
+
+impl_int_encodable!(u8);
+impl_int_encodable!(u16);
+impl_int_encodable!(u32);
+impl_int_encodable!(u64);
+impl_int_encodable!(i64);
+
+/// Length-prefixed byte strings (scripts and witness items).
+impl Encodable for Vec<u8> {
+    fn consensus_encode(&self, writer: &mut Vec<u8>) -> Result<usize, EncodingError> {
+        let len = CompactSize(self.len() as u64).consensus_encode(writer)?;
+        writer.extend_from_slice(self);
+        Ok(len + self.len())
+    }
+}
+
+impl Decodable for Vec<u8> {
+    fn consensus_decode(reader: &mut &[u8]) -> Result<Self, EncodingError> {
+        let CompactSize(len) = CompactSize::consensus_decode(reader)?;
+        Ok(take(reader, len as usize)?.to_vec())
+    }
+}
+
+fn decode_hex_field(field: &'static str, value: &str) -> Result<Vec<u8>, EncodingError> {
+    hex::decode(value).map_err(|_| EncodingError::InvalidHex {
+        field,
+        value: value.to_string(),
+    })
+}
+
+/// Converts a txid in display (big-endian) hex into its 32 internal-order bytes.
+pub fn txid_to_bytes(txid: &str) -> Result<[u8; 32], EncodingError> {
+    let mut bytes = decode_hex_field("txid", txid)?;
+    bytes.reverse();
+    let len = bytes.len();
+    bytes.try_into().map_err(|_| EncodingError::InvalidTxidLength(len))
+}
+
+/// Converts 32 internal-order txid bytes into display (big-endian) hex.
+pub fn txid_from_bytes(bytes: &[u8; 32]) -> String {
+    let mut reversed = *bytes;
+    reversed.reverse();
+    hex::encode(reversed)
+}
+
+impl Encodable for Vin {
+    fn consensus_encode(&self, writer: &mut Vec<u8>) -> Result<usize, EncodingError> {
+        let txid = txid_to_bytes(&self.txid)?;
+        writer.extend_from_slice(&txid);
+        let mut len = txid.len();
+        len += self.vout.consensus_encode(writer)?;
+        len += decode_hex_field("scriptsig", &self.scriptsig)?.consensus_encode(writer)?;
+        len += self.sequence.consensus_encode(writer)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for Vin {
+    fn consensus_decode(reader: &mut &[u8]) -> Result<Self, EncodingError> {
+        let txid: [u8; 32] = take(reader, 32)?.try_into().expect("took 32 bytes");
+        let vout = u32::consensus_decode(reader)?;
+        let scriptsig = Vec::<u8>::consensus_decode(reader)?;
+        let sequence = u32::consensus_decode(reader)?;
+        Ok(Vin {
+            txid: txid_from_bytes(&txid),
+            vout,
+            // Raw transactions do not carry the spent output; the loader fills it in.
+            prevout: Prevout::default(),
+            scriptsig_asm: disassemble(&scriptsig),
+            scriptsig: hex::encode(scriptsig),
+            witness: None,
+            sequence,
+            // Set by `Transaction::consensus_decode`, which knows whether the transaction is a coinbase
+            is_coinbase: false,
+            inner_redeemscript_asm: String::new(),
+            inner_witnessscript_asm: String::new(),
+        })
+    }
+}
+
+impl Encodable for Vout {
+    fn consensus_encode(&self, writer: &mut Vec<u8>) -> Result<usize, EncodingError> {
+        let mut len = self.value.consensus_encode(writer)?;
+        len += decode_hex_field("scriptpubkey", &self.scriptpubkey)?.consensus_encode(writer)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for Vout {
+    fn consensus_decode(reader: &mut &[u8]) -> Result<Self, EncodingError> {
+        let value = u64::consensus_decode(reader)?;
+        let value = i64::try_from(value)
+            .ok()
+            .filter(|value| *value <= MAX_MONEY)
+            .ok_or(EncodingError::AmountOutOfRange(value))?;
+        let scriptpubkey = Vec::<u8>::consensus_decode(reader)?;
+        Ok(Vout {
+            scriptpubkey_asm: disassemble(&scriptpubkey),
+            scriptpubkey_type: esplora_type(&scriptpubkey).to_string(),
+            scriptpubkey_address: script_address(&scriptpubkey),
+            scriptpubkey: hex::encode(scriptpubkey),
+            value,
+        })
+    }
+}
+
+impl<T: Encodable> Encodable for [T] {
+    fn consensus_encode(&self, writer: &mut Vec<u8>) -> Result<usize, EncodingError> {
+        let mut len = CompactSize(self.len() as u64).consensus_encode(writer)?;
+        for item in self {
+            len += item.consensus_encode(writer)?;
+        }
+        Ok(len)
+    }
+}
+
+fn decode_list<T: Decodable>(reader: &mut &[u8]) -> Result<Vec<T>, EncodingError> {
+    let CompactSize(count) = CompactSize::consensus_decode(reader)?;
+    // Every element takes at least one byte, so cap the allocation by what is left.
+    let mut items = Vec::with_capacity((count as usize).min(reader.len()));
+    for _ in 0..count {
+        items.push(T::consensus_decode(reader)?);
+    }
+    Ok(items)
+}
+
+/// Encodes a transaction, optionally in the BIP144 segwit layout:
+/// version | 0x00 marker | 0x01 flag | inputs | outputs | witness stacks | locktime
+fn encode_transaction(transaction: &Transaction, writer: &mut Vec<u8>, with_witness: bool) -> Result<usize, EncodingError> {
+    let mut len = transaction.version.consensus_encode(writer)?;
+    if with_witness {
+        writer.extend_from_slice(&[0x00, 0x01]);
+        len += 2;
+    }
+    len += transaction.vin.consensus_encode(writer)?;
+    len += transaction.vout.consensus_encode(writer)?;
+    if with_witness {
+        for vin in &transaction.vin {
+            let witness = vin.witness.as_deref().unwrap_or_default();
+            len += CompactSize(witness.len() as u64).consensus_encode(writer)?;
+            for item in witness {
+                len += decode_hex_field("witness", item)?.consensus_encode(writer)?;
+            }
+        }
+    }
+    len += transaction.locktime.consensus_encode(writer)?;
+    Ok(len)
+}
+
+/// Serializes a transaction without witness data; this is the form hashed into the txid.
+pub fn serialize_without_witness(transaction: &Transaction) -> Result<Vec<u8>, EncodingError> {
+    let mut bytes = Vec::new();
+    encode_transaction(transaction, &mut bytes, false)?;
+    Ok(bytes)
+}
+
+/// Uses the segwit layout whenever any input carries witness data.
+impl Encodable for Transaction {
+    fn consensus_encode(&self, writer: &mut Vec<u8>) -> Result<usize, EncodingError> {
+        encode_transaction(self, writer, self.has_witness())
+    }
+}
+
+impl Decodable for Transaction {
+    fn consensus_decode(reader: &mut &[u8]) -> Result<Self, EncodingError> {
+        let version = u32::consensus_decode(reader)?;
+        // A zero input count cannot start a valid transaction, so 0x00 here is the segwit marker
+        let segwit = reader.first() == Some(&0x00);
+        if segwit {
+            take(reader, 1)?;
+            let flag = u8::consensus_decode(reader)?;
+            if flag != 0x01 {
+                return Err(EncodingError::UnsupportedSegwitFlag(flag));
+            }
+        }
+        let mut vin: Vec<Vin> = decode_list(reader)?;
+        let vout = decode_list(reader)?;
+        if segwit {
+            for input in vin.iter_mut() {
+                let stack: Vec<Vec<u8>> = decode_list(reader)?;
+                if !stack.is_empty() {
+                    input.witness = Some(stack.iter().map(hex::encode).collect());
+                }
+            }
+            if vin.iter().all(|input| input.witness.is_none()) {
+                return Err(EncodingError::SuperfluousWitness);
+            }
+        }
+        let locktime = u32::consensus_decode(reader)?;
+        let is_coinbase = vin.len() == 1 && vin[0].has_null_outpoint();
+        if is_coinbase {
+            vin[0].is_coinbase = true;
+        }
+        Ok(Transaction {
+            version,
+            locktime,
+            vin,
+            vout,
+            is_coinbase,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Bitcoin's genesis coinbase transaction (txid 4a5e1e4b...).
+    const GENESIS_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+
+    #[test]
+    fn test_compact_size_round_trip() {
+        let cases: [(u64, &str); 6] = [
+            (0, "00"),
+            (0xfc, "fc"),
+            (0xfd, "fdfd00"),
+            (0xffff, "fdffff"),
+            (0x1_0000, "fe00000100"),
+            (0x0200_0000, "fe00000002"),
+        ];
+        for (value, expected) in cases {
+            let bytes = serialize(&CompactSize(value)).unwrap();
+            assert_eq!(hex::encode(&bytes), expected);
+            assert_eq!(deserialize::<CompactSize>(&bytes).unwrap(), CompactSize(value));
+        }
+    }
+
+    #[test]
+    fn test_compact_size_rejects_non_minimal() {
+        let bytes = hex::decode("fd0500").unwrap();
+        assert_eq!(deserialize::<CompactSize>(&bytes), Err(EncodingError::NonMinimalCompactSize));
+    }
+
+    #[test]
+    fn test_genesis_coinbase_round_trip() {
+        let bytes = hex::decode(GENESIS_COINBASE).unwrap();
+        let transaction: Transaction = deserialize(&bytes).unwrap();
+        assert!(transaction.is_coinbase);
+        assert_eq!(transaction.vout[0].value, 5_000_000_000);
+        assert_eq!(serialize(&transaction).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_large_values_and_counts() {
+        let bytes = hex::decode(GENESIS_COINBASE).unwrap();
+        let mut transaction: Transaction = deserialize(&bytes).unwrap();
+        transaction.vout[0].value = 2_100_000_000_000_000;
+        transaction.vin = vec![transaction.vin[0].clone(); 300];
+
+        let encoded = serialize(&transaction).unwrap();
+        // version, then a three-byte CompactSize for 300 inputs
+        assert_eq!(&encoded[4..7], &[0xfd, 0x2c, 0x01]);
+        let decoded: Transaction = deserialize(&encoded).unwrap();
+        assert_eq!(decoded.vin.len(), 300);
+        assert_eq!(decoded.vout[0].value, 2_100_000_000_000_000);
+    }
+
+    #[test]
+    fn test_amount_out_of_range() {
+        let bytes = hex::decode(GENESIS_COINBASE).unwrap();
+        let mut transaction: Transaction = deserialize(&bytes).unwrap();
+        for value in [-1, MAX_MONEY + 1] {
+            transaction.vout[0].value = value;
+            let encoded = serialize(&transaction).unwrap();
+            assert_eq!(deserialize::<Transaction>(&encoded), Err(EncodingError::AmountOutOfRange(value as u64)));
+        }
+    }
+
+    #[test]
+    fn test_segwit_round_trip() {
+        let json_data = std::fs::read_to_string(
+            "./mempool/0018c221bca3da35128baabe412a14c95b6864b2e6f7f7a8ffdd8eb0923dec49.json",
+        )
+        .unwrap();
+        let transaction: Transaction = serde_json::from_str(&json_data).unwrap();
+
+        let bytes = serialize(&transaction).unwrap();
+        assert_eq!(&bytes[4..6], &[0x00, 0x01]);
+        assert_eq!(bytes.len(), 223);
+        assert_eq!(serialize_without_witness(&transaction).unwrap().len(), 114);
+
+        let decoded: Transaction = deserialize(&bytes).unwrap();
+        assert_eq!(decoded.vin[0].witness, transaction.vin[0].witness);
+        assert_eq!(serialize(&decoded).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_superfluous_witness() {
+        let mut bytes = hex::decode(GENESIS_COINBASE).unwrap();
+        // Insert marker and flag, then an empty witness stack before the locktime
+        bytes.splice(4..4, [0x00, 0x01]);
+        let locktime_at = bytes.len() - 4;
+        bytes.insert(locktime_at, 0x00);
+        assert_eq!(deserialize::<Transaction>(&bytes), Err(EncodingError::SuperfluousWitness));
+    }
+
+    #[test]
+    fn test_truncated_transaction() {
+        let bytes = hex::decode(GENESIS_COINBASE).unwrap();
+        assert_eq!(
+            deserialize::<Transaction>(&bytes[..bytes.len() - 1]),
+            Err(EncodingError::UnexpectedEof)
+        );
+    }
+}
@@ -0,0 +1,1201 @@
+use std::fmt;
+
+use ripemd::Ripemd160;
+use secp256k1::{Parity, Scalar, Secp256k1, XOnlyPublicKey};
+use sha2::{Digest, Sha256};
+
+use crate::encoding::Encodable;
+use crate::script::{encode_script, parse_script, Instruction, Opcode, ScriptError};
+use crate::script_type::{witness_program, ScriptType};
+use crate::transaction_validator::tagged_hash;
+
+/// Largest script that may be executed, in bytes.
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/// Largest element that may be pushed onto the stack.
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+/// Most non-push opcodes a script may contain (public keys of CHECKMULTISIG count too).
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+/// Most elements the main and alt stacks may hold together.
+pub const MAX_STACK_SIZE: usize = 1000;
+/// Most public keys an OP_CHECKMULTISIG may take.
+pub const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+
+/// Script numbers are at most 4 bytes, except the lock time arguments.
+const DEFAULT_MAX_NUM_SIZE: usize = 4;
+const LOCK_TIME_MAX_NUM_SIZE: usize = 5;
+
+/// Disables the sequence lock of an OP_CHECKSEQUENCEVERIFY argument (BIP112).
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: i64 = 1 << 31;
+
+/// A taproot control block is the leaf version and parity byte and the internal key,
+/// followed by up to 128 Merkle path nodes (BIP341).
+const TAPROOT_CONTROL_BASE_SIZE: usize = 33;
+const TAPROOT_CONTROL_NODE_SIZE: usize = 32;
+const TAPROOT_CONTROL_MAX_NODE_COUNT: usize = 128;
+pub const TAPROOT_LEAF_MASK: u8 = 0xfe;
+/// The leaf version of BIP342 tapscript.
+pub const TAPROOT_LEAF_TAPSCRIPT: u8 = 0xc0;
+/// First byte of a taproot annex, the last of at least two witness items (BIP341).
+pub const ANNEX_TAG: u8 = 0x50;
+
+/// Tapscript signature budget: the witness size plus the offset, less the cost of each signature checked (BIP342).
+const VALIDATION_WEIGHT_OFFSET: i64 = 50;
+const VALIDATION_WEIGHT_PER_SIGOP_PASSED: i64 = 50;
+
+/// Which signature rules a script is executed under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigVersion {
+    /// Legacy scripts: scriptSig, scriptPubKey and P2SH redeem scripts.
+    Base,
+    /// Segwit v0 witness scripts (BIP143 signature hashes, no FindAndDelete).
+    WitnessV0,
+    /// Taproot key path spends (BIP341 signature hashes, BIP340 Schnorr signatures).
+    Taproot,
+    /// Taproot script path spends of leaf version 0xc0 (BIP342).
+    Tapscript,
+}
+
+/// What a taproot signature commits to besides the transaction, and the tapscript
+/// signature budget left.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionData {
+    /// The tapleaf hash of the executing script; `None` for key path spends.
+    pub tapleaf_hash: Option<[u8; 32]>,
+    /// Opcode position of the last executed OP_CODESEPARATOR, 0xffffffff before any.
+    pub code_separator_position: u32,
+    pub validation_weight_left: i64,
+    /// The annex of the spend, tag byte included.
+    pub annex: Option<Vec<u8>>,
+}
+
+impl Default for ExecutionData {
+    fn default() -> ExecutionData {
+        ExecutionData { tapleaf_hash: None, code_separator_position: u32::MAX, validation_weight_left: 0, annex: None }
+    }
+}
+
+/// Splits the annex off a taproot witness: with at least two items, a last item
+/// starting with 0x50 is the annex rather than part of the spend.
+pub fn split_annex(witness: &[Vec<u8>]) -> (&[Vec<u8>], Option<&[u8]>) {
+    match witness {
+        [rest @ .., last] if !rest.is_empty() && last.first() == Some(&ANNEX_TAG) => (rest, Some(last)),
+        _ => (witness, None),
+    }
+}
+
+/// The transaction-dependent checks a script can request.
+pub trait SignatureChecker {
+    /// Checks an ECDSA `signature` (DER with the sighash type byte appended) against
+    /// `public_key`, where `script_code` is the executed script from the last OP_CODESEPARATOR.
+    fn check_ecdsa_signature(&self, signature: &[u8], public_key: &[u8], script_code: &[u8], sig_version: SigVersion) -> bool;
+
+    /// Checks a BIP340 Schnorr `signature` (64 bytes, or 65 with an explicit sighash type)
+    /// against the 32-byte x-only `public_key`.
+    fn check_schnorr_signature(
+        &self,
+        _signature: &[u8],
+        _public_key: &[u8],
+        _sig_version: SigVersion,
+        _execution: &ExecutionData,
+    ) -> bool {
+        false
+    }
+
+    /// Whether the spending transaction satisfies an OP_CHECKLOCKTIMEVERIFY argument.
+    fn check_lock_time(&self, _lock_time: i64) -> bool {
+        false
+    }
+
+    /// Whether the spending input satisfies an OP_CHECKSEQUENCEVERIFY argument.
+    fn check_sequence(&self, _sequence: i64) -> bool {
+        false
+    }
+}
+
+/// Why script execution failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionError {
+    Parse(ScriptError),
+    ScriptSize,
+    PushSize,
+    OpCount,
+    StackSize,
+    PubkeyCount,
+    SigCount,
+    DisabledOpcode(Opcode),
+    BadOpcode(Opcode),
+    OpReturn,
+    UnbalancedConditional,
+    InvalidStackOperation,
+    InvalidAltstackOperation,
+    NumberOverflow,
+    /// A VERIFY-style opcode found a false value.
+    Verify(Opcode),
+    NegativeLockTime,
+    UnsatisfiedLockTime,
+    /// The OP_CHECKMULTISIG dummy element was not empty (BIP147).
+    NullDummy,
+    /// The script finished with an empty stack or a false top element.
+    EvalFalse,
+    /// A P2SH scriptSig contained something other than pushes.
+    SigPushOnly,
+    /// A v0 witness program that is neither 20 nor 32 bytes.
+    WitnessProgramWrongLength,
+    /// A P2WSH spend with an empty witness.
+    WitnessProgramWitnessEmpty,
+    /// The witness does not match the program: wrong P2WPKH item count or witness script hash.
+    WitnessProgramMismatch,
+    /// A native witness spend with a non-empty scriptSig.
+    WitnessMalleated,
+    /// A nested witness spend whose scriptSig is not exactly the push of the witness program.
+    WitnessMalleatedP2sh,
+    /// Witness data on an input that does not spend a witness program.
+    WitnessUnexpected,
+    /// A witness script left more than one element on the stack.
+    CleanStack,
+    /// A non-empty Schnorr signature that does not verify, or has an invalid size or sighash type.
+    SchnorrSig,
+    /// A taproot control block that is not 33 bytes plus a whole number of (at most 128) 32-byte nodes.
+    TaprootWrongControlSize,
+    /// A tapscript OP_IF/OP_NOTIF argument other than empty or 0x01.
+    TapscriptMinimalIf,
+    /// OP_CHECKMULTISIG(VERIFY) in tapscript, where OP_CHECKSIGADD replaces it.
+    TapscriptCheckMultiSig,
+    /// A tapscript checked more signatures than its witness size pays for.
+    TapscriptValidationWeight,
+    /// A tapscript signature check with an empty public key.
+    TapscriptEmptyPubkey,
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutionError::Parse(error) => write!(f, "{}", error),
+            ExecutionError::ScriptSize => write!(f, "script is larger than {} bytes", MAX_SCRIPT_SIZE),
+            ExecutionError::PushSize => write!(f, "push is larger than {} bytes", MAX_SCRIPT_ELEMENT_SIZE),
+            ExecutionError::OpCount => write!(f, "more than {} opcodes", MAX_OPS_PER_SCRIPT),
+            ExecutionError::StackSize => write!(f, "more than {} stack elements", MAX_STACK_SIZE),
+            ExecutionError::PubkeyCount => write!(f, "invalid public key count"),
+            ExecutionError::SigCount => write!(f, "invalid signature count"),
+            ExecutionError::DisabledOpcode(opcode) => write!(f, "disabled opcode {:?}", opcode),
+            ExecutionError::BadOpcode(opcode) => write!(f, "bad opcode {:?}", opcode),
+            ExecutionError::OpReturn => write!(f, "OP_RETURN was executed"),
+            ExecutionError::UnbalancedConditional => write!(f, "unbalanced conditional"),
+            ExecutionError::InvalidStackOperation => write!(f, "operation needs more stack elements"),
+            ExecutionError::InvalidAltstackOperation => write!(f, "operation needs more alt stack elements"),
+            ExecutionError::NumberOverflow => write!(f, "script number overflow"),
+            ExecutionError::Verify(opcode) => write!(f, "{:?} failed", opcode),
+            ExecutionError::NegativeLockTime => write!(f, "negative lock time"),
+            ExecutionError::UnsatisfiedLockTime => write!(f, "lock time requirement not satisfied"),
+            ExecutionError::NullDummy => write!(f, "OP_CHECKMULTISIG dummy element is not empty"),
+            ExecutionError::EvalFalse => write!(f, "script evaluated to false"),
+            ExecutionError::SigPushOnly => write!(f, "P2SH scriptSig is not push-only"),
+            ExecutionError::WitnessProgramWrongLength => write!(f, "witness program has the wrong length"),
+            ExecutionError::WitnessProgramWitnessEmpty => write!(f, "witness is empty"),
+            ExecutionError::WitnessProgramMismatch => write!(f, "witness does not match the witness program"),
+            ExecutionError::WitnessMalleated => write!(f, "native witness spend has a non-empty scriptSig"),
+            ExecutionError::WitnessMalleatedP2sh => write!(f, "nested witness spend has a scriptSig other than the program push"),
+            ExecutionError::WitnessUnexpected => write!(f, "witness data on a non-witness spend"),
+            ExecutionError::CleanStack => write!(f, "witness script left extra stack elements"),
+            ExecutionError::SchnorrSig => write!(f, "invalid Schnorr signature"),
+            ExecutionError::TaprootWrongControlSize => write!(f, "taproot control block has the wrong size"),
+            ExecutionError::TapscriptMinimalIf => write!(f, "tapscript OP_IF argument is not minimal"),
+            ExecutionError::TapscriptCheckMultiSig => write!(f, "OP_CHECKMULTISIG is disabled in tapscript"),
+            ExecutionError::TapscriptValidationWeight => write!(f, "tapscript exceeds its signature budget"),
+            ExecutionError::TapscriptEmptyPubkey => write!(f, "tapscript public key is empty"),
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
+
+/// Decodes a script number: little-endian with a sign bit, at most `max_len` bytes.
+pub fn decode_num(bytes: &[u8], max_len: usize) -> Result<i64, ExecutionError> {
+    if bytes.len() > max_len {
+        return Err(ExecutionError::NumberOverflow);
+    }
+    let Some(&last) = bytes.last() else {
+        return Ok(0);
+    };
+    let mut value = 0i64;
+    for (i, &byte) in bytes.iter().enumerate() {
+        value |= (byte as i64) << (8 * i);
+    }
+    if last & 0x80 != 0 {
+        // Clear the sign bit and negate
+        Ok(-(value & !(0x80i64 << (8 * (bytes.len() - 1)))))
+    } else {
+        Ok(value)
+    }
+}
+
+/// Encodes a script number in its minimal form.
+pub fn encode_num(value: i64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut magnitude = value.unsigned_abs();
+    while magnitude > 0 {
+        bytes.push((magnitude & 0xff) as u8);
+        magnitude >>= 8;
+    }
+    // The top bit of the last byte is the sign; add a byte if it is already taken
+    if let Some(last) = bytes.last_mut() {
+        if *last & 0x80 != 0 {
+            bytes.push(if value < 0 { 0x80 } else { 0x00 });
+        } else if value < 0 {
+            *last |= 0x80;
+        }
+    }
+    bytes
+}
+
+/// Any non-zero value is true, except negative zero.
+pub fn cast_to_bool(bytes: &[u8]) -> bool {
+    match bytes.split_last() {
+        Some((&last, rest)) => rest.iter().any(|&byte| byte != 0) || (last != 0 && last != 0x80),
+        None => false,
+    }
+}
+
+fn is_disabled(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        Opcode::Cat
+            | Opcode::Substr
+            | Opcode::Left
+            | Opcode::Right
+            | Opcode::Invert
+            | Opcode::And
+            | Opcode::Or
+            | Opcode::Xor
+            | Opcode::Mul2
+            | Opcode::Div2
+            | Opcode::Mul
+            | Opcode::Div
+            | Opcode::Mod
+            | Opcode::LShift
+            | Opcode::RShift
+    )
+}
+
+fn pop(stack: &mut Vec<Vec<u8>>) -> Result<Vec<u8>, ExecutionError> {
+    stack.pop().ok_or(ExecutionError::InvalidStackOperation)
+}
+
+// The element `depth` places from the top (1 is the top)
+fn top(stack: &[Vec<u8>], depth: usize) -> Result<&Vec<u8>, ExecutionError> {
+    stack
+        .len()
+        .checked_sub(depth)
+        .map(|index| &stack[index])
+        .ok_or(ExecutionError::InvalidStackOperation)
+}
+
+fn pop_num(stack: &mut Vec<Vec<u8>>) -> Result<i64, ExecutionError> {
+    decode_num(&pop(stack)?, DEFAULT_MAX_NUM_SIZE)
+}
+
+fn push_bool(stack: &mut Vec<Vec<u8>>, value: bool) {
+    stack.push(if value { vec![1] } else { vec![] });
+}
+
+// Removes every push of `signature` from a legacy scriptCode, as Bitcoin Core's FindAndDelete does
+fn find_and_delete(script_code: &[u8], signature: &[u8]) -> Vec<u8> {
+    let pattern = Instruction::push(signature);
+    match parse_script(script_code) {
+        Ok(instructions) => {
+            let kept: Vec<Instruction> = instructions.into_iter().filter(|instruction| *instruction != pattern).collect();
+            encode_script(&kept)
+        }
+        Err(_) => script_code.to_vec(),
+    }
+}
+
+// Opcodes that make a tapscript succeed as soon as it is parsed, reserved for upgrades (BIP342)
+fn is_op_success(opcode: Opcode) -> bool {
+    matches!(opcode.to_u8(), 80 | 98 | 126..=129 | 131..=134 | 137..=138 | 141..=142 | 149..=153 | 187..=254)
+}
+
+// Whether a tapscript has an OP_SUCCESSx before the point, if any, where it stops parsing
+fn contains_op_success(script: &[u8]) -> bool {
+    let parsed = match parse_script(script) {
+        Ok(instructions) => instructions,
+        Err(ScriptError::TruncatedPush { offset }) => parse_script(&script[..offset]).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    parsed
+        .iter()
+        .any(|instruction| matches!(instruction, Instruction::Op(opcode) if is_op_success(*opcode)))
+}
+
+// A BIP342 signature check: an empty signature fails, any other must be valid for a 32-byte key
+fn check_tapscript_signature(
+    signature: &[u8],
+    public_key: &[u8],
+    execution: &mut ExecutionData,
+    checker: &dyn SignatureChecker,
+) -> Result<bool, ExecutionError> {
+    let success = !signature.is_empty();
+    if success {
+        execution.validation_weight_left -= VALIDATION_WEIGHT_PER_SIGOP_PASSED;
+        if execution.validation_weight_left < 0 {
+            return Err(ExecutionError::TapscriptValidationWeight);
+        }
+    }
+    if public_key.is_empty() {
+        return Err(ExecutionError::TapscriptEmptyPubkey);
+    }
+    // Other key sizes are reserved for upgrades and accept any signature
+    if success
+        && public_key.len() == 32
+        && !checker.check_schnorr_signature(signature, public_key, SigVersion::Tapscript, execution)
+    {
+        return Err(ExecutionError::SchnorrSig);
+    }
+    Ok(success)
+}
+
+/// Executes `script` on `stack`.
+pub fn eval_script(
+    stack: &mut Vec<Vec<u8>>,
+    script: &[u8],
+    sig_version: SigVersion,
+    checker: &dyn SignatureChecker,
+) -> Result<(), ExecutionError> {
+    execute_script(stack, script, sig_version, &mut ExecutionData::default(), checker)
+}
+
+// Executes `script` on `stack`, recording code separators and spending the tapscript signature budget in `execution`
+fn execute_script(
+    stack: &mut Vec<Vec<u8>>,
+    script: &[u8],
+    sig_version: SigVersion,
+    execution: &mut ExecutionData,
+    checker: &dyn SignatureChecker,
+) -> Result<(), ExecutionError> {
+    let is_tapscript = sig_version == SigVersion::Tapscript;
+    if !is_tapscript && script.len() > MAX_SCRIPT_SIZE {
+        return Err(ExecutionError::ScriptSize);
+    }
+    let instructions = parse_script(script).map_err(ExecutionError::Parse)?;
+
+    let mut alt_stack: Vec<Vec<u8>> = Vec::new();
+    // One entry per open IF: whether its current branch executes
+    let mut exec_stack: Vec<bool> = Vec::new();
+    let mut op_count = 0;
+    // Byte offsets of the current instruction and of the code after the last OP_CODESEPARATOR
+    let mut offset = 0;
+    let mut code_separator = 0;
+
+    for (position, instruction) in instructions.iter().enumerate() {
+        let executing = exec_stack.iter().all(|&branch| branch);
+        offset += instruction.encoded_len();
+
+        let opcode = match instruction {
+            Instruction::Push(_, data) => {
+                if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                    return Err(ExecutionError::PushSize);
+                }
+                if executing {
+                    stack.push(data.clone());
+                }
+                if stack.len() + alt_stack.len() > MAX_STACK_SIZE {
+                    return Err(ExecutionError::StackSize);
+                }
+                continue;
+            }
+            Instruction::Op(opcode) => *opcode,
+        };
+
+        // Tapscript has no opcode limit, only the signature budget
+        if !is_tapscript && opcode.to_u8() > Opcode::PushNum(16).to_u8() {
+            op_count += 1;
+            if op_count > MAX_OPS_PER_SCRIPT {
+                return Err(ExecutionError::OpCount);
+            }
+        }
+        // Disabled opcodes fail the script even in an unexecuted branch
+        if is_disabled(opcode) {
+            return Err(ExecutionError::DisabledOpcode(opcode));
+        }
+        let is_conditional = (Opcode::If.to_u8()..=Opcode::EndIf.to_u8()).contains(&opcode.to_u8());
+        if !executing && !is_conditional {
+            continue;
+        }
+
+        match opcode {
+            // Constants
+            Opcode::PushNumNeg1 => stack.push(encode_num(-1)),
+            Opcode::PushNum(number) => stack.push(encode_num(number as i64)),
+
+            // Control flow
+            Opcode::Nop
+            | Opcode::Nop1
+            | Opcode::Nop4
+            | Opcode::Nop5
+            | Opcode::Nop6
+            | Opcode::Nop7
+            | Opcode::Nop8
+            | Opcode::Nop9
+            | Opcode::Nop10 => {}
+            Opcode::CheckLockTimeVerify => {
+                let lock_time = decode_num(top(stack, 1)?, LOCK_TIME_MAX_NUM_SIZE)?;
+                if lock_time < 0 {
+                    return Err(ExecutionError::NegativeLockTime);
+                }
+                if !checker.check_lock_time(lock_time) {
+                    return Err(ExecutionError::UnsatisfiedLockTime);
+                }
+            }
+            Opcode::CheckSequenceVerify => {
+                let sequence = decode_num(top(stack, 1)?, LOCK_TIME_MAX_NUM_SIZE)?;
+                if sequence < 0 {
+                    return Err(ExecutionError::NegativeLockTime);
+                }
+                // With the disable flag set the opcode behaves as a NOP
+                if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG == 0 && !checker.check_sequence(sequence) {
+                    return Err(ExecutionError::UnsatisfiedLockTime);
+                }
+            }
+            Opcode::If | Opcode::NotIf => {
+                let mut value = false;
+                if executing {
+                    let argument = pop(stack)?;
+                    if is_tapscript && !(argument.is_empty() || argument == [1]) {
+                        return Err(ExecutionError::TapscriptMinimalIf);
+                    }
+                    value = cast_to_bool(&argument);
+                    if opcode == Opcode::NotIf {
+                        value = !value;
+                    }
+                }
+                exec_stack.push(value);
+            }
+            Opcode::Else => {
+                let branch = exec_stack.last_mut().ok_or(ExecutionError::UnbalancedConditional)?;
+                *branch = !*branch;
+            }
+            Opcode::EndIf => {
+                exec_stack.pop().ok_or(ExecutionError::UnbalancedConditional)?;
+            }
+            Opcode::Verify => {
+                if !cast_to_bool(&pop(stack)?) {
+                    return Err(ExecutionError::Verify(opcode));
+                }
+            }
+            Opcode::Return => return Err(ExecutionError::OpReturn),
+
+            // Stack operations
+            Opcode::ToAltStack => alt_stack.push(pop(stack)?),
+            Opcode::FromAltStack => stack.push(alt_stack.pop().ok_or(ExecutionError::InvalidAltstackOperation)?),
+            Opcode::TwoDrop => {
+                pop(stack)?;
+                pop(stack)?;
+            }
+            Opcode::TwoDup => {
+                let (a, b) = (top(stack, 2)?.clone(), top(stack, 1)?.clone());
+                stack.push(a);
+                stack.push(b);
+            }
+            Opcode::ThreeDup => {
+                let (a, b, c) = (top(stack, 3)?.clone(), top(stack, 2)?.clone(), top(stack, 1)?.clone());
+                stack.push(a);
+                stack.push(b);
+                stack.push(c);
+            }
+            Opcode::TwoOver => {
+                let (a, b) = (top(stack, 4)?.clone(), top(stack, 3)?.clone());
+                stack.push(a);
+                stack.push(b);
+            }
+            Opcode::TwoRot => {
+                top(stack, 6)?;
+                let index = stack.len() - 6;
+                let a = stack.remove(index);
+                let b = stack.remove(index);
+                stack.push(a);
+                stack.push(b);
+            }
+            Opcode::TwoSwap => {
+                top(stack, 4)?;
+                let len = stack.len();
+                stack.swap(len - 4, len - 2);
+                stack.swap(len - 3, len - 1);
+            }
+            Opcode::IfDup => {
+                let value = top(stack, 1)?.clone();
+                if cast_to_bool(&value) {
+                    stack.push(value);
+                }
+            }
+            Opcode::Depth => stack.push(encode_num(stack.len() as i64)),
+            Opcode::Drop => {
+                pop(stack)?;
+            }
+            Opcode::Dup => stack.push(top(stack, 1)?.clone()),
+            Opcode::Nip => {
+                top(stack, 2)?;
+                let index = stack.len() - 2;
+                stack.remove(index);
+            }
+            Opcode::Over => stack.push(top(stack, 2)?.clone()),
+            Opcode::Pick | Opcode::Roll => {
+                let depth = pop_num(stack)?;
+                if depth < 0 || depth as usize >= stack.len() {
+                    return Err(ExecutionError::InvalidStackOperation);
+                }
+                let index = stack.len() - 1 - depth as usize;
+                let value = if opcode == Opcode::Roll { stack.remove(index) } else { stack[index].clone() };
+                stack.push(value);
+            }
+            Opcode::Rot => {
+                top(stack, 3)?;
+                let index = stack.len() - 3;
+                let value = stack.remove(index);
+                stack.push(value);
+            }
+            Opcode::Swap => {
+                top(stack, 2)?;
+                let len = stack.len();
+                stack.swap(len - 2, len - 1);
+            }
+            Opcode::Tuck => {
+                top(stack, 2)?;
+                let value = top(stack, 1)?.clone();
+                let index = stack.len() - 2;
+                stack.insert(index, value);
+            }
+            Opcode::Size => stack.push(encode_num(top(stack, 1)?.len() as i64)),
+
+            // Bitwise logic
+            Opcode::Equal | Opcode::EqualVerify => {
+                let b = pop(stack)?;
+                let a = pop(stack)?;
+                let equal = a == b;
+                if opcode == Opcode::EqualVerify {
+                    if !equal {
+                        return Err(ExecutionError::Verify(opcode));
+                    }
+                } else {
+                    push_bool(stack, equal);
+                }
+            }
+
+            // Numeric
+            Opcode::Add1 | Opcode::Sub1 | Opcode::Negate | Opcode::Abs | Opcode::Not | Opcode::NotEqual0 => {
+                let value = pop_num(stack)?;
+                let result = match opcode {
+                    Opcode::Add1 => value + 1,
+                    Opcode::Sub1 => value - 1,
+                    Opcode::Negate => -value,
+                    Opcode::Abs => value.abs(),
+                    Opcode::Not => (value == 0) as i64,
+                    _ => (value != 0) as i64,
+                };
+                stack.push(encode_num(result));
+            }
+            Opcode::Add
+            | Opcode::Sub
+            | Opcode::BoolAnd
+            | Opcode::BoolOr
+            | Opcode::NumEqual
+            | Opcode::NumEqualVerify
+            | Opcode::NumNotEqual
+            | Opcode::LessThan
+            | Opcode::GreaterThan
+            | Opcode::LessThanOrEqual
+            | Opcode::GreaterThanOrEqual
+            | Opcode::Min
+            | Opcode::Max => {
+                let b = pop_num(stack)?;
+                let a = pop_num(stack)?;
+                let result = match opcode {
+                    Opcode::Add => a + b,
+                    Opcode::Sub => a - b,
+                    Opcode::BoolAnd => (a != 0 && b != 0) as i64,
+                    Opcode::BoolOr => (a != 0 || b != 0) as i64,
+                    Opcode::NumEqual | Opcode::NumEqualVerify => (a == b) as i64,
+                    Opcode::NumNotEqual => (a != b) as i64,
+                    Opcode::LessThan => (a < b) as i64,
+                    Opcode::GreaterThan => (a > b) as i64,
+                    Opcode::LessThanOrEqual => (a <= b) as i64,
+                    Opcode::GreaterThanOrEqual => (a >= b) as i64,
+                    Opcode::Min => a.min(b),
+                    _ => a.max(b),
+                };
+                if opcode == Opcode::NumEqualVerify {
+                    if result == 0 {
+                        return Err(ExecutionError::Verify(opcode));
+                    }
+                } else {
+                    stack.push(encode_num(result));
+                }
+            }
+            Opcode::Within => {
+                let max = pop_num(stack)?;
+                let min = pop_num(stack)?;
+                let value = pop_num(stack)?;
+                push_bool(stack, min <= value && value < max);
+            }
+
+            // Crypto
+            Opcode::Ripemd160 => {
+                let value = pop(stack)?;
+                stack.push(Ripemd160::digest(value).to_vec());
+            }
+            Opcode::Sha1 => {
+                let value = pop(stack)?;
+                stack.push(crypto_hash::digest(crypto_hash::Algorithm::SHA1, &value));
+            }
+            Opcode::Sha256 => {
+                let value = pop(stack)?;
+                stack.push(Sha256::digest(value).to_vec());
+            }
+            Opcode::Hash160 => {
+                let value = pop(stack)?;
+                stack.push(Ripemd160::digest(Sha256::digest(value)).to_vec());
+            }
+            Opcode::Hash256 => {
+                let value = pop(stack)?;
+                stack.push(Sha256::digest(Sha256::digest(value)).to_vec());
+            }
+            Opcode::CodeSeparator => {
+                code_separator = offset;
+                execution.code_separator_position = position as u32;
+            }
+            Opcode::CheckSig | Opcode::CheckSigVerify if is_tapscript => {
+                let public_key = pop(stack)?;
+                let signature = pop(stack)?;
+                let valid = check_tapscript_signature(&signature, &public_key, execution, checker)?;
+                if opcode == Opcode::CheckSigVerify {
+                    if !valid {
+                        return Err(ExecutionError::Verify(opcode));
+                    }
+                } else {
+                    push_bool(stack, valid);
+                }
+            }
+            Opcode::CheckSigAdd if is_tapscript => {
+                let public_key = pop(stack)?;
+                let number = pop_num(stack)?;
+                let signature = pop(stack)?;
+                let valid = check_tapscript_signature(&signature, &public_key, execution, checker)?;
+                stack.push(encode_num(number + valid as i64));
+            }
+            Opcode::CheckMultiSig | Opcode::CheckMultiSigVerify if is_tapscript => {
+                return Err(ExecutionError::TapscriptCheckMultiSig);
+            }
+            Opcode::CheckSig | Opcode::CheckSigVerify => {
+                let public_key = pop(stack)?;
+                let signature = pop(stack)?;
+                let mut script_code = script[code_separator..].to_vec();
+                if sig_version == SigVersion::Base {
+                    script_code = find_and_delete(&script_code, &signature);
+                }
+                let valid = !signature.is_empty()
+                    && checker.check_ecdsa_signature(&signature, &public_key, &script_code, sig_version);
+                if opcode == Opcode::CheckSigVerify {
+                    if !valid {
+                        return Err(ExecutionError::Verify(opcode));
+                    }
+                } else {
+                    push_bool(stack, valid);
+                }
+            }
+            Opcode::CheckMultiSig | Opcode::CheckMultiSigVerify => {
+                let key_count = decode_num(top(stack, 1)?, DEFAULT_MAX_NUM_SIZE)?;
+                if key_count < 0 || key_count as usize > MAX_PUBKEYS_PER_MULTISIG {
+                    return Err(ExecutionError::PubkeyCount);
+                }
+                let key_count = key_count as usize;
+                op_count += key_count;
+                if op_count > MAX_OPS_PER_SCRIPT {
+                    return Err(ExecutionError::OpCount);
+                }
+                // Depths of the first key and signature below the top of the stack
+                let first_key = 2;
+                let sig_count_depth = first_key + key_count;
+                let sig_count = decode_num(top(stack, sig_count_depth)?, DEFAULT_MAX_NUM_SIZE)?;
+                if sig_count < 0 || sig_count as usize > key_count {
+                    return Err(ExecutionError::SigCount);
+                }
+                let sig_count = sig_count as usize;
+                let first_sig = sig_count_depth + 1;
+                // Everything consumed, plus the extra element the original implementation pops
+                let consumed = first_sig + sig_count;
+                top(stack, consumed)?;
+
+                let mut script_code = script[code_separator..].to_vec();
+                if sig_version == SigVersion::Base {
+                    for depth in first_sig..first_sig + sig_count {
+                        script_code = find_and_delete(&script_code, &stack[stack.len() - depth]);
+                    }
+                }
+
+                // Signatures must match keys in order; each key is tried once
+                let (mut key_depth, mut sig_depth) = (first_key, first_sig);
+                let (mut keys_left, mut sigs_left) = (key_count, sig_count);
+                let mut success = true;
+                while success && sigs_left > 0 {
+                    let signature = &stack[stack.len() - sig_depth];
+                    let public_key = &stack[stack.len() - key_depth];
+                    if !signature.is_empty()
+                        && checker.check_ecdsa_signature(signature, public_key, &script_code, sig_version)
+                    {
+                        sig_depth += 1;
+                        sigs_left -= 1;
+                    }
+                    key_depth += 1;
+                    keys_left -= 1;
+                    if sigs_left > keys_left {
+                        success = false;
+                    }
+                }
+
+                stack.truncate(stack.len() - (consumed - 1));
+                // The extra element must be empty (BIP147 NULLDUMMY)
+                if !pop(stack)?.is_empty() {
+                    return Err(ExecutionError::NullDummy);
+                }
+                if opcode == Opcode::CheckMultiSigVerify {
+                    if !success {
+                        return Err(ExecutionError::Verify(opcode));
+                    }
+                } else {
+                    push_bool(stack, success);
+                }
+            }
+
+            // Reserved, unassigned and (outside tapscript) OP_CHECKSIGADD
+            _ => return Err(ExecutionError::BadOpcode(opcode)),
+        }
+
+        if stack.len() + alt_stack.len() > MAX_STACK_SIZE {
+            return Err(ExecutionError::StackSize);
+        }
+    }
+
+    if !exec_stack.is_empty() {
+        return Err(ExecutionError::UnbalancedConditional);
+    }
+    Ok(())
+}
+
+// A spend succeeds when execution leaves a true value on top of the stack
+fn check_true(stack: &[Vec<u8>]) -> Result<(), ExecutionError> {
+    match stack.last() {
+        Some(value) if cast_to_bool(value) => Ok(()),
+        _ => Err(ExecutionError::EvalFalse),
+    }
+}
+
+// Runs a witness script or tapscript on the witness stack; it must leave exactly one true element
+fn execute_witness_script(
+    mut stack: Vec<Vec<u8>>,
+    script: &[u8],
+    sig_version: SigVersion,
+    execution: &mut ExecutionData,
+    checker: &dyn SignatureChecker,
+) -> Result<(), ExecutionError> {
+    if sig_version == SigVersion::Tapscript {
+        // OP_SUCCESSx overrides every other rule, the element size limit included
+        if contains_op_success(script) {
+            return Ok(());
+        }
+        if stack.len() > MAX_STACK_SIZE {
+            return Err(ExecutionError::StackSize);
+        }
+    }
+    if stack.iter().any(|item| item.len() > MAX_SCRIPT_ELEMENT_SIZE) {
+        return Err(ExecutionError::PushSize);
+    }
+    execute_script(&mut stack, script, sig_version, execution, checker)?;
+    if stack.len() != 1 {
+        return Err(ExecutionError::CleanStack);
+    }
+    check_true(&stack)
+}
+
+/// Verifies `witness` against a witness program (BIP141).
+///
+/// For v0, a 20-byte program is a P2WPKH key hash spent with `<signature> <public key>`, and
+/// a 32-byte program is the SHA-256 of the witness script, which is the last witness item.
+///
+/// A native v1 32-byte program is a taproot output key (BIP341). A witness with a single
+/// item is a key path spend, that item being a Schnorr signature for the key; otherwise the
+/// last two items are a leaf script and the control block committing to it. Either may be
+/// followed by an annex.
+/// Other versions and lengths, and taproot nested in P2SH, are not defined yet and succeed.
+pub fn verify_witness_program(
+    witness: &[Vec<u8>],
+    version: u8,
+    program: &[u8],
+    is_p2sh: bool,
+    checker: &dyn SignatureChecker,
+) -> Result<(), ExecutionError> {
+    if version == 1 && program.len() == 32 && !is_p2sh {
+        return verify_taproot(witness, program, checker);
+    }
+    if version != 0 {
+        return Ok(());
+    }
+    match program.len() {
+        20 => {
+            if witness.len() != 2 {
+                return Err(ExecutionError::WitnessProgramMismatch);
+            }
+            // OP_DUP OP_HASH160 <program> OP_EQUALVERIFY OP_CHECKSIG
+            let mut script_code = vec![0x76, 0xa9, 0x14];
+            script_code.extend_from_slice(program);
+            script_code.extend_from_slice(&[0x88, 0xac]);
+            execute_witness_script(witness.to_vec(), &script_code, SigVersion::WitnessV0, &mut ExecutionData::default(), checker)
+        }
+        32 => {
+            let (witness_script, stack) = witness.split_last().ok_or(ExecutionError::WitnessProgramWitnessEmpty)?;
+            if Sha256::digest(witness_script).as_slice() != program {
+                return Err(ExecutionError::WitnessProgramMismatch);
+            }
+            execute_witness_script(stack.to_vec(), witness_script, SigVersion::WitnessV0, &mut ExecutionData::default(), checker)
+        }
+        _ => Err(ExecutionError::WitnessProgramWrongLength),
+    }
+}
+
+// The BIP341 hash of a leaf script: its version, then the script with a length prefix
+fn tapleaf_hash(leaf_version: u8, script: &[u8]) -> [u8; 32] {
+    let mut data = vec![leaf_version];
+    // Writing to a Vec cannot fail
+    let _ = script.to_vec().consensus_encode(&mut data);
+    tagged_hash("TapLeaf", &data)
+}
+
+// Whether the output key is the control block's internal key tweaked with the Merkle root
+// reached from `tapleaf_hash` through the control block's path
+fn verify_taproot_commitment(control: &[u8], output_key: &[u8], tapleaf_hash: [u8; 32]) -> bool {
+    let mut node = tapleaf_hash;
+    for sibling in control[TAPROOT_CONTROL_BASE_SIZE..].chunks(TAPROOT_CONTROL_NODE_SIZE) {
+        // Branches hash their children in lexicographic order
+        let children = if node.as_slice() < sibling { [&node[..], sibling] } else { [sibling, &node[..]] };
+        node = tagged_hash("TapBranch", &children.concat());
+    }
+    let internal_key = &control[1..TAPROOT_CONTROL_BASE_SIZE];
+    let tweak = tagged_hash("TapTweak", &[internal_key, &node[..]].concat());
+    let (Ok(internal_key), Ok(output_key), Ok(tweak)) =
+        (XOnlyPublicKey::from_slice(internal_key), XOnlyPublicKey::from_slice(output_key), Scalar::from_be_bytes(tweak))
+    else {
+        return false;
+    };
+    let parity = if control[0] & 1 == 1 { Parity::Odd } else { Parity::Even };
+    internal_key.tweak_add_check(&Secp256k1::verification_only(), &output_key, parity, tweak)
+}
+
+// Verifies a taproot key path or script path spend of `output_key` (BIP341)
+fn verify_taproot(witness: &[Vec<u8>], output_key: &[u8], checker: &dyn SignatureChecker) -> Result<(), ExecutionError> {
+    // Signatures commit to the annex, which is otherwise ignored
+    let (spend, annex) = split_annex(witness);
+    let key_path = ExecutionData { annex: annex.map(<[u8]>::to_vec), ..ExecutionData::default() };
+    let (stack, script, control) = match spend {
+        [] => return Err(ExecutionError::WitnessProgramWitnessEmpty),
+        [signature] if checker.check_schnorr_signature(signature, output_key, SigVersion::Taproot, &key_path) => {
+            return Ok(());
+        }
+        [_] => return Err(ExecutionError::SchnorrSig),
+        [stack @ .., script, control] => (stack, script, control),
+    };
+    let node_bytes = control.len().checked_sub(TAPROOT_CONTROL_BASE_SIZE);
+    if !node_bytes.is_some_and(|len| {
+        len % TAPROOT_CONTROL_NODE_SIZE == 0 && len / TAPROOT_CONTROL_NODE_SIZE <= TAPROOT_CONTROL_MAX_NODE_COUNT
+    }) {
+        return Err(ExecutionError::TaprootWrongControlSize);
+    }
+    let leaf_version = control[0] & TAPROOT_LEAF_MASK;
+    let leaf_hash = tapleaf_hash(leaf_version, script);
+    if !verify_taproot_commitment(control, output_key, leaf_hash) {
+        return Err(ExecutionError::WitnessProgramMismatch);
+    }
+    // Other leaf versions are reserved for upgrades
+    if leaf_version != TAPROOT_LEAF_TAPSCRIPT {
+        return Ok(());
+    }
+    let mut execution = ExecutionData {
+        tapleaf_hash: Some(leaf_hash),
+        // The budget counts the whole witness, annex included
+        validation_weight_left: witness.consensus_encode(&mut Vec::new()).unwrap_or(0) as i64 + VALIDATION_WEIGHT_OFFSET,
+        ..key_path
+    };
+    execute_witness_script(stack.to_vec(), script, SigVersion::Tapscript, &mut execution, checker)
+}
+
+/// Runs `script_sig` and then `script_pubkey` on the resulting stack; the spend is
+/// valid if that leaves a true value on top.
+///
+/// When `script_pubkey` is P2SH (BIP16), the scriptSig must be push-only and its last
+/// push is the redeem script: once the scriptPubKey has checked its HASH160, the redeem
+/// script runs on the pushes before it and must leave a true value as well.
+///
+/// A witness program, native or as the P2SH redeem script, is then verified against
+/// `witness`. Any other spend must come with an empty witness.
+pub fn verify_script(
+    script_sig: &[u8],
+    script_pubkey: &[u8],
+    witness: &[Vec<u8>],
+    checker: &dyn SignatureChecker,
+) -> Result<(), ExecutionError> {
+    let mut stack = Vec::new();
+    eval_script(&mut stack, script_sig, SigVersion::Base, checker)?;
+    let p2sh_stack = stack.clone();
+    eval_script(&mut stack, script_pubkey, SigVersion::Base, checker)?;
+    check_true(&stack)?;
+
+    let mut witness_checked = false;
+    if let Some((version, program)) = witness_program(script_pubkey) {
+        if !script_sig.is_empty() {
+            return Err(ExecutionError::WitnessMalleated);
+        }
+        verify_witness_program(witness, version, program, false, checker)?;
+        witness_checked = true;
+    } else if ScriptType::classify(script_pubkey) == ScriptType::P2sh {
+        let push_only = parse_script(script_sig).is_ok_and(|instructions| instructions.iter().all(Instruction::is_push));
+        if !push_only {
+            return Err(ExecutionError::SigPushOnly);
+        }
+        let mut stack = p2sh_stack;
+        // Not empty: the scriptPubKey just hashed it
+        let redeem_script = pop(&mut stack)?;
+        eval_script(&mut stack, &redeem_script, SigVersion::Base, checker)?;
+        check_true(&stack)?;
+
+        if let Some((version, program)) = witness_program(&redeem_script) {
+            if script_sig != encode_script(&[Instruction::push(&redeem_script)]) {
+                return Err(ExecutionError::WitnessMalleatedP2sh);
+            }
+            verify_witness_program(witness, version, program, true, checker)?;
+            witness_checked = true;
+        }
+    }
+
+    if !witness_checked && !witness.is_empty() {
+        return Err(ExecutionError::WitnessUnexpected);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Accepts an ECDSA signature when it equals the public key with 0x01 appended,
+    // and a Schnorr signature when it is the public key twice
+    struct FakeChecker;
+
+    impl SignatureChecker for FakeChecker {
+        fn check_ecdsa_signature(&self, signature: &[u8], public_key: &[u8], _script_code: &[u8], _sig_version: SigVersion) -> bool {
+            signature.split_last().is_some_and(|(&hash_type, rest)| hash_type == 0x01 && rest == public_key)
+        }
+
+        fn check_schnorr_signature(
+            &self,
+            signature: &[u8],
+            public_key: &[u8],
+            _sig_version: SigVersion,
+            _execution: &ExecutionData,
+        ) -> bool {
+            signature == [public_key, public_key].concat()
+        }
+    }
+
+    fn run(script: &[u8]) -> Result<Vec<Vec<u8>>, ExecutionError> {
+        let mut stack = Vec::new();
+        eval_script(&mut stack, script, SigVersion::Base, &FakeChecker)?;
+        Ok(stack)
+    }
+
+    #[test]
+    fn test_script_numbers() {
+        for value in [0, 1, -1, 127, 128, -128, 255, 256, 32767, -32768, 2147483647, -2147483647] {
+            assert_eq!(decode_num(&encode_num(value), 5).unwrap(), value);
+        }
+        assert_eq!(encode_num(128), vec![0x80, 0x00]);
+        assert_eq!(encode_num(-1), vec![0x81]);
+        // Non-minimal encodings are still numbers
+        assert_eq!(decode_num(&[0x01, 0x00], 4).unwrap(), 1);
+        assert_eq!(decode_num(&[0x00, 0x80], 4).unwrap(), 0);
+        assert_eq!(decode_num(&[1, 2, 3, 4, 5], 4), Err(ExecutionError::NumberOverflow));
+
+        assert!(!cast_to_bool(&[]));
+        assert!(!cast_to_bool(&[0x00, 0x80]));
+        assert!(cast_to_bool(&[0x80, 0x00]));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        // 2 3 ADD 5 NUMEQUAL
+        assert_eq!(run(&[0x52, 0x53, 0x93, 0x55, 0x9c]).unwrap(), vec![vec![1]]);
+        // 5 2 SUB 1SUB NEGATE ABS -> 2
+        assert_eq!(run(&[0x55, 0x52, 0x94, 0x8c, 0x8f, 0x90]).unwrap(), vec![vec![2]]);
+        // 3 2 5 WITHIN
+        assert_eq!(run(&[0x53, 0x52, 0x55, 0xa5]).unwrap(), vec![vec![1]]);
+        // 5-byte operands overflow
+        assert_eq!(run(&[0x05, 1, 2, 3, 4, 5, 0x8b]), Err(ExecutionError::NumberOverflow));
+    }
+
+    #[test]
+    fn test_conditionals() {
+        // 1 IF 2 ELSE 3 ENDIF
+        assert_eq!(run(&[0x51, 0x63, 0x52, 0x67, 0x53, 0x68]).unwrap(), vec![vec![2]]);
+        // 0 IF 2 ELSE 3 ENDIF
+        assert_eq!(run(&[0x00, 0x63, 0x52, 0x67, 0x53, 0x68]).unwrap(), vec![vec![3]]);
+        // 0 NOTIF 0 IF RETURN ENDIF 4 ENDIF: nested unexecuted OP_RETURN is fine
+        assert_eq!(run(&[0x00, 0x64, 0x00, 0x63, 0x6a, 0x68, 0x54, 0x68]).unwrap(), vec![vec![4]]);
+        assert_eq!(run(&[0x51, 0x63]), Err(ExecutionError::UnbalancedConditional));
+        assert_eq!(run(&[0x68]), Err(ExecutionError::UnbalancedConditional));
+        // Disabled and VERIF opcodes fail even when not executed
+        assert_eq!(run(&[0x00, 0x63, 0x7e, 0x68]), Err(ExecutionError::DisabledOpcode(Opcode::Cat)));
+        assert_eq!(run(&[0x00, 0x63, 0x65, 0x68]), Err(ExecutionError::BadOpcode(Opcode::VerIf)));
+        // Reserved opcodes only fail when executed
+        assert!(run(&[0x00, 0x63, 0x50, 0x68]).is_ok());
+        assert_eq!(run(&[0x50]), Err(ExecutionError::BadOpcode(Opcode::Reserved)));
+    }
+
+    #[test]
+    fn test_stack_operations() {
+        // 1 2 3 ROT -> 2 3 1
+        assert_eq!(run(&[0x51, 0x52, 0x53, 0x7b]).unwrap(), vec![vec![2], vec![3], vec![1]]);
+        // 1 2 3 2 PICK -> 1 2 3 1
+        assert_eq!(run(&[0x51, 0x52, 0x53, 0x52, 0x79]).unwrap().last().unwrap(), &vec![1]);
+        // 1 2 3 2 ROLL -> 2 3 1
+        assert_eq!(run(&[0x51, 0x52, 0x53, 0x52, 0x7a]).unwrap(), vec![vec![2], vec![3], vec![1]]);
+        // 1 2 TUCK -> 2 1 2
+        assert_eq!(run(&[0x51, 0x52, 0x7d]).unwrap(), vec![vec![2], vec![1], vec![2]]);
+        // 1 2 3 4 2SWAP -> 3 4 1 2
+        assert_eq!(run(&[0x51, 0x52, 0x53, 0x54, 0x72]).unwrap(), vec![vec![3], vec![4], vec![1], vec![2]]);
+        // 1 TOALTSTACK 2 FROMALTSTACK -> 2 1
+        assert_eq!(run(&[0x51, 0x6b, 0x52, 0x6c]).unwrap(), vec![vec![2], vec![1]]);
+        assert_eq!(run(&[0x6c]), Err(ExecutionError::InvalidAltstackOperation));
+        assert_eq!(run(&[0x76]), Err(ExecutionError::InvalidStackOperation));
+        assert_eq!(run(&[0x51, 0x52, 0x79]), Err(ExecutionError::InvalidStackOperation));
+    }
+
+    #[test]
+    fn test_hash_opcodes() {
+        // SHA256 of the empty string
+        let stack = run(&[0x00, 0xa8]).unwrap();
+        assert_eq!(hex::encode(&stack[0]), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        let stack = run(&[0x00, 0xa7]).unwrap();
+        assert_eq!(hex::encode(&stack[0]), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        let stack = run(&[0x00, 0xa6]).unwrap();
+        assert_eq!(hex::encode(&stack[0]), "9c1185a5c5e9fc54612808977ee8f548b2258d31");
+        let stack = run(&[0x00, 0xa9]).unwrap();
+        assert_eq!(hex::encode(&stack[0]), "b472a266d0bd89c13706a4132ccfb16f7c3b9fcb");
+    }
+
+    #[test]
+    fn test_limits() {
+        // 202 NOPs
+        assert_eq!(run(&[0x61; 202]), Err(ExecutionError::OpCount));
+        assert!(run(&[0x61; 201]).is_ok());
+        // 521-byte push
+        let mut script = vec![0x4d, 0x09, 0x02];
+        script.extend([0u8; 521]);
+        assert_eq!(run(&script), Err(ExecutionError::PushSize));
+        // 1001 stack elements
+        assert_eq!(run(&[0x51; 1001]), Err(ExecutionError::StackSize));
+        assert_eq!(run(&[0x61; MAX_SCRIPT_SIZE + 1]), Err(ExecutionError::ScriptSize));
+        assert_eq!(run(&[0x6a]), Err(ExecutionError::OpReturn));
+    }
+
+    #[test]
+    fn test_checksig_and_checkmultisig() {
+        let key_a = vec![0xaa; 33];
+        let key_b = vec![0xbb; 33];
+        let key_c = vec![0xcc; 33];
+        let sig = |key: &Vec<u8>| [key.as_slice(), &[0x01]].concat();
+
+        // <sig> <key> CHECKSIG
+        let mut script = encode_script(&[Instruction::push(&sig(&key_a)), Instruction::push(&key_a)]);
+        script.push(0xac);
+        assert_eq!(run(&script).unwrap(), vec![vec![1]]);
+
+        // 2-of-3 with signatures in key order succeeds
+        let multisig = |sigs: &[Vec<u8>], dummy: &[u8]| {
+            let mut instructions = vec![Instruction::push(dummy)];
+            instructions.extend(sigs.iter().map(|signature| Instruction::push(signature)));
+            instructions.push(Instruction::Op(Opcode::PushNum(2)));
+            instructions.extend([&key_a, &key_b, &key_c].iter().map(|key| Instruction::push(key)));
+            instructions.push(Instruction::Op(Opcode::PushNum(3)));
+            instructions.push(Instruction::Op(Opcode::CheckMultiSig));
+            encode_script(&instructions)
+        };
+        assert_eq!(run(&multisig(&[sig(&key_a), sig(&key_c)], &[])).unwrap(), vec![vec![1]]);
+        // Out of order fails without error
+        assert_eq!(run(&multisig(&[sig(&key_c), sig(&key_a)], &[])).unwrap(), vec![Vec::<u8>::new()]);
+        // The dummy element must be empty
+        assert_eq!(run(&multisig(&[sig(&key_a), sig(&key_b)], &[0x00])), Err(ExecutionError::NullDummy));
+        // 21 keys is too many
+        assert_eq!(run(&[0x00, 0x00, 0x01, 21, 0xae]), Err(ExecutionError::PubkeyCount));
+    }
+
+    #[test]
+    fn test_find_and_delete() {
+        let signature = vec![0x30, 0x01, 0x01];
+        let mut script = encode_script(&[Instruction::push(&signature), Instruction::Op(Opcode::Drop)]);
+        script.push(0xac);
+        assert_eq!(find_and_delete(&script, &signature), vec![0x75, 0xac]);
+    }
+
+    #[test]
+    fn test_tapscript() {
+        let run_tapscript = |stack: Vec<Vec<u8>>, script: &[u8], weight: i64| {
+            let mut execution = ExecutionData { validation_weight_left: weight, ..ExecutionData::default() };
+            execute_witness_script(stack, script, SigVersion::Tapscript, &mut execution, &FakeChecker)
+        };
+        let (key_a, key_b, key_c) = (vec![0xaa; 32], vec![0xbb; 32], vec![0xcc; 32]);
+        let sig = |key: &Vec<u8>| [key.as_slice(), key].concat();
+
+        // 2-of-3 with OP_CHECKSIGADD: <key_a> CHECKSIG <key_b> CHECKSIGADD <key_c> CHECKSIGADD 2 NUMEQUAL
+        let script = encode_script(&[
+            Instruction::push(&key_a),
+            Instruction::Op(Opcode::CheckSig),
+            Instruction::push(&key_b),
+            Instruction::Op(Opcode::CheckSigAdd),
+            Instruction::push(&key_c),
+            Instruction::Op(Opcode::CheckSigAdd),
+            Instruction::Op(Opcode::PushNum(2)),
+            Instruction::Op(Opcode::NumEqual),
+        ]);
+        let stack = vec![sig(&key_c), vec![], sig(&key_a)];
+        assert_eq!(run_tapscript(stack.clone(), &script, 100), Ok(()));
+        // Each non-empty signature costs 50 from the budget
+        assert_eq!(run_tapscript(stack, &script, 99), Err(ExecutionError::TapscriptValidationWeight));
+        // An invalid signature is an error rather than a false result
+        assert_eq!(run_tapscript(vec![sig(&key_b), vec![], sig(&key_a)], &script, 100), Err(ExecutionError::SchnorrSig));
+        // Empty keys are rejected, keys of other sizes accept anything
+        assert_eq!(run_tapscript(vec![vec![1]], &[0x00, 0xac], 100), Err(ExecutionError::TapscriptEmptyPubkey));
+        assert_eq!(run_tapscript(vec![vec![1]], &[0x01, 0x02, 0xac], 100), Ok(()));
+
+        // OP_CHECKMULTISIG is disabled and OP_IF takes only minimal booleans
+        assert_eq!(run_tapscript(vec![], &[0x00, 0x00, 0x00, 0xae], 100), Err(ExecutionError::TapscriptCheckMultiSig));
+        assert_eq!(run_tapscript(vec![vec![2]], &[0x63, 0x51, 0x68], 100), Err(ExecutionError::TapscriptMinimalIf));
+        assert_eq!(run_tapscript(vec![vec![1]], &[0x63, 0x51, 0x68], 100), Ok(()));
+
+        // OP_SUCCESSx succeeds before anything else is checked, even after OP_RETURN or before a bad push
+        assert_eq!(run_tapscript(vec![], &[0x6a, 0x50], 0), Ok(()));
+        assert_eq!(run_tapscript(vec![vec![0; 521]], &[0xbb, 0x4c], 0), Ok(()));
+        assert!(run_tapscript(vec![], &[0x4c, 0x50], 0).is_err());
+
+        // No opcode or script size limit
+        let mut nops = vec![0x61; MAX_SCRIPT_SIZE + 1];
+        nops.push(0x51);
+        assert_eq!(run_tapscript(vec![], &nops, 0), Ok(()));
+        // but a clean stack
+        assert_eq!(run_tapscript(vec![vec![1]], &[0x51], 0), Err(ExecutionError::CleanStack));
+    }
+
+    #[test]
+    fn test_code_separator_position() {
+        // OP_1 OP_CODESEPARATOR OP_DROP OP_CODESEPARATOR OP_1, the second separator being opcode 3
+        let mut execution = ExecutionData::default();
+        let mut stack = Vec::new();
+        execute_script(&mut stack, &[0x51, 0xab, 0x75, 0xab, 0x51], SigVersion::Tapscript, &mut execution, &FakeChecker).unwrap();
+        assert_eq!(execution.code_separator_position, 3);
+    }
+}
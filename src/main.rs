@@ -3,38 +3,54 @@ extern crate rand;
 extern crate secp256k1;
 extern crate hex;
 
-use hex::FromHex;
 use std::fs::File;
 use std::io::{BufWriter, Write};
+use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
-use std::time::{Instant, Duration};
-use rayon::iter::plumbing::bridge;
-use rayon::string;
-use sha2::digest::generic_array::sequence;
 use sha2::{Digest, Sha256};
-use serde_json;
 use serde::{Deserialize, Serialize};
-use rand::Rng;
-use secp256k1::{Secp256k1, Message, PublicKey, ecdsa::Signature};
+
+mod coinbase;
+use coinbase::{add_witness_commitment, build_coinbase, total_fees};
+
+mod config;
+use config::{Config, HashScheme, MempoolFormat, OutputFormat};
+
+mod encoding;
+use encoding::{serialize, txid_from_bytes, txid_to_bytes, Encodable, EncodingError};
+
+mod interpreter;
+
+mod script;
+
+mod script_type;
+
+mod sighash;
+
+mod merkle;
+use merkle::MerkleProof;
+
+mod policy;
+
+mod report;
+use report::{write_report, ReportEntry};
+
+mod target;
+use target::Target;
 
 mod transaction_loader;
 use transaction_loader::Transaction;
-use transaction_loader::Vin;
-use transaction_loader::Vout;
-use transaction_loader::Prevout;
-use transaction_loader::fetch_transactions_from_mempool;
+use transaction_loader::load_mempool_entries;
+use transaction_loader::load_raw_hex_entries;
+use transaction_loader::MempoolEntry;
+use transaction_loader::sort_by_fee;
+use transaction_loader::load_prevouts;
 
 mod transaction_validator;
-use transaction_validator::verify_p2pkh_address;
-use transaction_validator::verify_v0_p2wpkh_address;
-use transaction_validator::verify_v0_p2wsh_address;
-use transaction_validator::verify_v1_p2tr_address;
-use transaction_validator::verify_p2sh_address;
-use transaction_validator::verify_unknown;
-use transaction_validator::verify_scriptsig_p2pkh;
-use transaction_validator::verify_scriptsig_p2sh;
-use transaction_validator::verify_witness_p2wpkh;
-use transaction_validator::verify_witness_p2wsh;
+use transaction_validator::validate_transaction;
+
+mod verifier;
+use verifier::VerifierRegistry;
 
 
 
@@ -42,28 +58,64 @@ use transaction_validator::verify_witness_p2wsh;
 pub struct BlockHeader {
     pub previous_block_hash: String,
     pub nonce: u32,
-    pub timestamp: u64,
+    pub timestamp: u32,
     pub merkle_root: String,
     pub height: u64,
     pub version: u32,
-    pub bits: String,
+    pub bits: u32,
+}
+
+impl BlockHeader {
+    // The 80-byte consensus header:
+    // version | previous block hash | merkle root | timestamp | bits | nonce
+    // Hashes are stored in display order and written in internal byte order.
+    pub fn serialize(&self) -> Result<[u8; 80], EncodingError> {
+        let previous_block_hash = txid_to_bytes(&self.previous_block_hash)?;
+        let merkle_root = txid_to_bytes(&self.merkle_root)?;
+
+        let mut header = [0u8; 80];
+        header[0..4].copy_from_slice(&self.version.to_le_bytes());
+        header[4..36].copy_from_slice(&previous_block_hash);
+        header[36..68].copy_from_slice(&merkle_root);
+        header[68..72].copy_from_slice(&self.timestamp.to_le_bytes());
+        header[72..76].copy_from_slice(&self.bits.to_le_bytes());
+        header[76..80].copy_from_slice(&self.nonce.to_le_bytes());
+        Ok(header)
+    }
+
+    // Double SHA-256 of the serialized header, in display order
+    pub fn block_hash(&self) -> Result<String, EncodingError> {
+        Ok(txid_from_bytes(&transaction_validator::double_sha256(&self.serialize()?)))
+    }
+
+    // The proof-of-work target encoded by `bits`
+    pub fn target(&self) -> Result<Target, target::CompactTargetError> {
+        Target::from_compact(self.bits)
+    }
 }
 
+/// Header version: the BIP9 top bits with no deployment signalled, which also
+/// satisfies the version 2-4 minimums of BIP34, BIP66 and BIP65.
+pub const BLOCK_VERSION: u32 = 0x2000_0000;
+
 #[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
 pub struct Block {
     pub block_header: BlockHeader,
+    // Every transaction in the block, coinbase first
+    pub transactions: Vec<Transaction>,
     pub transactions_ids: Vec<String>,
 }
 
 impl Block {
-    pub fn new(previous_block_hash: String, height: u64, merkle_root: String, transactions_ids: Vec<String>) -> Block {
+    pub fn new(previous_block_hash: String, height: u64, merkle_root: String, target: Target, transactions: Vec<Transaction>, transactions_ids: Vec<String>) -> Block {
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("Time went backwards")
             .as_secs();
-        
-        // if target is = "0000ffff00000000000000000000000000000000000000000000000000000000"
-        //then bits must be = "1f00ffff"
+        // Header timestamps are 32 bits, which last until 2106
+        let timestamp = u32::try_from(timestamp).expect("the clock is before 2106");
+
+        // bits is always derived from the target, so the two cannot disagree
         Block {
             block_header: BlockHeader {
                 previous_block_hash,
@@ -71,284 +123,74 @@ impl Block {
                 timestamp,
                 merkle_root,
                 height,
-                version: 1,
-                bits: "1f00ffff".to_string(),
+                version: BLOCK_VERSION,
+                bits: target.to_compact(),
             },
+            transactions,
             transactions_ids,
         }
     }
-    pub fn to_output_string(&self) -> String {
-        let mut output = String::new();
-        let mut header= String::new();
-
-        header.push_str(reverse_byte_order(&int_to_hex(self.block_header.version)).as_str());
-        header.push_str(&format!("{}", reverse_byte_order(self.block_header.previous_block_hash.as_str())));
-        header.push_str(&format!("{}", reverse_byte_order(self.block_header.merkle_root.as_str())));
-        header.push_str(reverse_byte_order(&int_to_hex(self.block_header.timestamp as u32)).as_str());
-        header.push_str(&format!("{}", reverse_byte_order(self.block_header.bits.as_str())));
-        header.push_str(reverse_byte_order(&int_to_hex(self.block_header.nonce)).as_str());
-        // Format block header
-        output += &format!("{}", header);
-
-        //output += &format!("{}", "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000");
-       // output += &format!("\n{}", "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b");
-        // Format transaction IDs
-        for txid in &self.transactions_ids {
-            output += &format!("\n{}", txid);
-        }
-
-        output
-    }
-}
-
-fn bytes_to_hex(bytes: usize) -> String {
-    format!("{:02x}", bytes)
-}
-
-fn int_to_hex(value: u32) -> String {
-    format!("{:08x}", value)
-}
-fn double_sha256(input: &str) -> String {
-    // Compute the first SHA256 hash
-    let mut hasher = Sha256::new();
-    hasher.update(input.as_bytes());
-    let hash1 = hasher.finalize();
-
-    // Compute the second SHA256 hash
-    let mut hasher = Sha256::new();
-    hasher.update(hash1);
-    let hash2 = hasher.finalize();
-
-    // Convert the hash to a hexadecimal string
-    let hex_hash: String = hash2.iter().map(|byte| format!("{:02x}", byte)).collect();
-    hex_hash
-}
-//serialize the transaction
-fn serialize_transaction(transaction: &Transaction) -> String {
-    let mut serialized_transaction = String::new();
-
-    // Serialize version
-    serialized_transaction.push_str(&int_to_hex(transaction.version));
-
-    // Serialize input count
-    let input_count: u32 = transaction.vin.len().try_into().unwrap();
-    serialized_transaction.push_str(&bytes_to_hex(input_count as usize));
-
-    // Serialize each input
-    for vin in &transaction.vin {
-        // Serialize txid
-        serialized_transaction.push_str(&format!("{}", vin.txid));
-
-        // Serialize vout
-        serialized_transaction.push_str(&int_to_hex(vin.vout));
-
-        // Serialize scriptsig_size
-        let scriptsig_size = vin.scriptsig.len()/2;
-        serialized_transaction.push_str(&bytes_to_hex(scriptsig_size));
-
-        // Serialize scriptsig
-        serialized_transaction.push_str(&format!("{}", vin.scriptsig));
-
-        // Serialize sequence
-        serialized_transaction.push_str(&int_to_hex(vin.sequence));
-    }
-
-    // Serialize output count
-    let output_count = transaction.vout.len().try_into().unwrap();
-    serialized_transaction.push_str(&int_to_hex(output_count));
-
-    // Serialize each output
-    for vout in &transaction.vout {
-        // Serialize value
-        serialized_transaction.push_str(&int_to_hex(vout.value as u32));
-
-        // Serialize scriptpubkey_size
-        let scriptpubkey_size = vout.scriptpubkey.len()/2;
-        serialized_transaction.push_str(&bytes_to_hex(scriptpubkey_size));
-
-        // Serialize scriptpubkey
-        serialized_transaction.push_str(&format!("{}", vout.scriptpubkey));
+    pub fn coinbase(&self) -> &Transaction {
+        &self.transactions[0]
     }
 
-    // Serialize locktime
-    serialized_transaction.push_str(&int_to_hex(transaction.locktime));
-
-    serialized_transaction
-}
-//non-segwit message
-fn non_segwit_message(transaction: &Transaction)->String{
-    let mut message = String::new();
-    message.push_str(&serialize_transaction(transaction));
-    message.push_str("01000000");
-    return message;
-}
-fn segwit_message(transaction: &Transaction, vin_current: &Vin)->String{
-    let mut serialized_transaction = String::new();
-
-    // Serialize version
-    serialized_transaction.push_str(&int_to_hex(transaction.version));
-
-    let mut inputs = String::new();
-    for vin in &transaction.vin {
-        // Serialize txid
-        inputs.push_str(&format!("{}", vin.txid));
-
-        // Serialize vout
-        inputs.push_str(&int_to_hex(vin.vout));
+    // Inclusion proof for `txid` against this block's merkle root, if the block contains it
+    pub fn merkle_proof(&self, txid: &str) -> Option<MerkleProof> {
+        MerkleProof::new(&self.transactions_ids, txid).expect("block txids are 32 bytes of hex")
     }
-    let hash_inputs = double_sha256(&inputs);
 
-    let mut sequences = String::new();
-    for vin in &transaction.vin {
-        // Serialize sequence
-        sequences.push_str(&int_to_hex(vin.sequence));
+    // Hex of the full serialized block, as accepted by `submitblock`
+    pub fn to_hex(&self) -> Result<String, EncodingError> {
+        Ok(hex::encode(serialize(self)?))
     }
-    let hash_sequences = double_sha256(&sequences);
-
-    let mut input = String::new();
-    input.push_str(&vin_current.txid);
-    input.push_str(&int_to_hex(vin_current.vout));
-
-    let parts: Vec<&str> = vin_current.prevout.scriptpubkey_asm.split_whitespace().collect();
-    let public_key_hash = parts[2].to_string();
-    let mut scriptcode = String::new();
-    scriptcode.push_str(&format!("1976a914{}88ac", public_key_hash));
 
-    let mut amount = String::new();
-    amount.push_str(&int_to_hex(vin_current.prevout.value as u32));
+    pub fn to_output_string(&self) -> Result<String, EncodingError> {
+        let mut output = String::new();
 
-    let mut sequence = String::new();
-    sequence.push_str(&int_to_hex(vin_current.sequence));
+        // Format block header
+        output += &hex::encode(self.block_header.serialize()?);
 
-    let mut outputs = String::new();
-    // Serialize each output
-    for vout in &transaction.vout {
-        // Serialize value
-        outputs.push_str(&int_to_hex(vout.value as u32));
+        // Serialized coinbase transaction
+        let coinbase = serialize(self.coinbase())?;
+        output += &format!("\n{}", hex::encode(coinbase));
 
-        // Serialize scriptpubkey_size
-        let scriptpubkey_size = vout.scriptpubkey.len()/2;
-        outputs.push_str(&bytes_to_hex(scriptpubkey_size));
+        // Format transaction IDs, coinbase first
+        for txid in &self.transactions_ids {
+            output += &format!("\n{}", txid);
+        }
 
-        // Serialize scriptpubkey
-        outputs.push_str(&format!("{}", vout.scriptpubkey));
+        Ok(output)
     }
-
-    let hash_outputs = double_sha256(&outputs);
-
-    let locktime = &int_to_hex(transaction.locktime);
-
-    serialized_transaction.push_str(&hash_inputs);
-    serialized_transaction.push_str(&hash_sequences);
-    serialized_transaction.push_str(&input);
-    serialized_transaction.push_str(&scriptcode);
-    serialized_transaction.push_str(&amount);
-    serialized_transaction.push_str(&sequence);
-    serialized_transaction.push_str(&hash_outputs);
-    serialized_transaction.push_str(&locktime);
-
-    let preimage = serialized_transaction;
-    let message = double_sha256(&preimage);
-
-    return message;
 }
 
-
-
-//double hash of each transaction
-fn hash_transaction(serialized_transaction: &str) -> String {
-    // Create a SHA-256 hasher
-    let mut hasher = Sha256::new();
-
-    // Update the hasher with the serialized transaction bytes
-    hasher.update(serialized_transaction.as_bytes());
-
-    // Calculate the hash
-    let hashed_bytes1 = hasher.finalize();
-
-    let mut hasher = Sha256::new();
-
-    // Update the hasher with the serialized transaction bytes
-    hasher.update(hashed_bytes1);
-
-    // Calculate the hash
-    let hashed_bytes = hasher.finalize();
-    // Convert the hash bytes to hexadecimal string
-    let hashed_hex_string = format!("{:02x}", hashed_bytes);
-
-    hashed_hex_string
+impl Encodable for Block {
+    // header | transaction count | transactions, with witness data
+    fn consensus_encode(&self, writer: &mut Vec<u8>) -> Result<usize, EncodingError> {
+        writer.extend_from_slice(&self.block_header.serialize()?);
+        Ok(80 + self.transactions.as_slice().consensus_encode(writer)?)
+    }
 }
 
-//reverse the byte order
-fn reverse_byte_order(input: &str) -> String {
-    // Convert the input string to bytes
-    let hex_string = input.to_owned();
 
-    // Convert hexadecimal string to byte slice
-    let byte_slice = Vec::from_hex(hex_string).unwrap();
 
-    // Reverse the byte slice
-    let mut reverse_byte_slice = Vec::new();
-    for &byte in byte_slice.iter().rev() {
-        reverse_byte_slice.push(byte);
-    }
-
-    // Convert byte slice back to hexadecimal string
-    let reverse_hex_string = hex::encode(reverse_byte_slice);
-
-    // Print the result
-    reverse_hex_string
-}
 //hashes of transactions
-pub fn hashes_of_transactions(transactions: &[Transaction]) -> Vec<String> {
+pub fn hashes_of_transactions(transactions: &[Transaction]) -> Result<Vec<String>, EncodingError> {
     let mut hashes = Vec::new();
 
-    // Iterate over each transaction
+    // Iterate over each transaction and collect its txid
     for transaction in transactions {
-        // Hash the transaction
-        let serialized_transaction = serialize_transaction(transaction);
-        let hash1 = hash_transaction(&serialized_transaction);
-        let hash = reverse_byte_order(&hash1);
-        // Collect the hash
-        hashes.push(hash);
+        hashes.push(transaction.txid()?);
     }
     // Return the vector of hashes
-    hashes
+    Ok(hashes)
 }
-//merkle root calculation
-pub fn calculate_merkle_root(transaction_hashes: &[String]) -> String {
-    if transaction_hashes.is_empty() {
-        return String::new();
-    }
-
-    let mut hashes = transaction_hashes.to_vec();
-
-    while hashes.len() > 1 {
-        if hashes.len() % 2 != 0 {
-            hashes.push(hashes.last().unwrap().clone());
-        }
-
-        let mut new_hashes = Vec::new();
-        for i in (0..hashes.len()).step_by(2) {
-            if i + 1 < hashes.len() {
-                let concatenated = format!("{}{}", hashes[i], hashes[i + 1]);
-                let mut hasher = Sha256::new();
-                hasher.update(concatenated.as_bytes());
-                let hash_bytes = hasher.finalize();
-    
-                // Convert the hash bytes to a hexadecimal string
-                let mut hash_hex = String::new();
-                for byte in hash_bytes {
-                    hash_hex.push_str(&format!("{:02x}", byte));
-                }
-                new_hashes.push(hash_hex.clone());
-            } else {
-                new_hashes.push(hashes[i].clone());
-            }
-        }
-        hashes = new_hashes;
+//merkle root calculation over display-order txids, coinbase first
+pub fn calculate_merkle_root(transaction_hashes: &[String]) -> Result<String, EncodingError> {
+    let mut hashes = Vec::new();
+    for hash in transaction_hashes {
+        hashes.push(txid_to_bytes(hash)?);
     }
-    hashes[0].clone()
+    Ok(merkle::merkle_root(&hashes).map(|root| txid_from_bytes(&root)).unwrap_or_default())
 }
 
 // Define the SHA-256 compression function
@@ -415,8 +257,14 @@ pub fn merkle_damgard_util(message: &[u8], salt: &[u8], iterations: usize) -> St
     hex::encode(state)
 }
 
-// Function to serialize the Block object, extract relevant fields, and pass them to merkle_damgard_util
-pub fn calculate_block_hash(block: &Block) -> String {
+// Block hash as used by Bitcoin: double SHA-256 of the 80-byte header
+pub fn calculate_block_hash(block: &Block) -> Result<String, EncodingError> {
+    block.block_header.block_hash()
+}
+
+// Legacy hash, kept only for HashScheme::Legacy: serializes the Block object, extracts
+// relevant fields, and passes them to merkle_damgard_util. Not valid proof of work for Bitcoin.
+pub fn calculate_legacy_block_hash(block: &Block) -> String {
     // Serialize the Block object into a byte representation
     let block_bytes = serde_json::to_vec(block).expect("Failed to serialize block");
 
@@ -424,11 +272,11 @@ pub fn calculate_block_hash(block: &Block) -> String {
     let header = &block.block_header;
     let previous_block_hash = &header.previous_block_hash;
     let nonce = header.nonce.to_be_bytes();
-    let timestamp = header.timestamp.to_be_bytes();
+    let timestamp = u64::from(header.timestamp).to_be_bytes();
     let merkle_root = &header.merkle_root;
     let height = header.height.to_be_bytes();
     let version = header.version.to_be_bytes();
-    let difficulty_target = &header.bits;
+    let difficulty_target = format!("{:08x}", header.bits);
 
     // Concatenate the relevant fields into a single byte vector
     let mut data = Vec::new();
@@ -444,237 +292,118 @@ pub fn calculate_block_hash(block: &Block) -> String {
     merkle_damgard_util(&block_bytes, &data, 1000) // Example iterations: 1000
 }
 
+// Whether a display-order block hash is at or below the target
+pub fn meets_target(block_hash: &str, target: &Target) -> bool {
+    txid_to_bytes(block_hash).is_ok_and(|hash| target.is_met_by(hash))
+}
+
 // Mining function
-pub fn mine_block(transactions: &Vec<Transaction>) -> Block {
-    // Store the transactions
-    let transaction_hashes = hashes_of_transactions(transactions);
-    
-    // Calculate the Merkle root
-    let merkle_root = calculate_merkle_root(&transaction_hashes);
+pub fn mine_block(transactions: &[Transaction], config: &Config) -> Block {
+    let height = config.height;
+    let previous_block_hash = config.previous_block_hash.clone();
+
+    // The coinbase claims the subsidy plus every included fee and always comes first
+    let mut coinbase = build_coinbase(height, total_fees(transactions), &config.payout_address);
+    // Blocks with segwit spends must commit to their witnesses (BIP141)
+    add_witness_commitment(&mut coinbase, transactions).expect("loaded transactions always serialize");
+    let mut transaction_hashes = vec![coinbase.txid().expect("coinbase always serializes")];
+    transaction_hashes.extend(hashes_of_transactions(transactions).expect("loaded transactions always serialize"));
 
-    // Generate random height and previous block hash
-    let height = 0;
-    let previous_block_hash = format!("{}", "0000000000000000000000000000000000000000000000000000000000000000");
+    // Calculate the Merkle root
+    let merkle_root = calculate_merkle_root(&transaction_hashes).expect("txids are 32 bytes of hex");
 
     // Create a block with the generated parameters
-    let mut block = Block::new(previous_block_hash.clone(), height, merkle_root.clone(), transaction_hashes);
+    let mut block_transactions = vec![coinbase];
+    block_transactions.extend_from_slice(transactions);
+    let mut block = Block::new(previous_block_hash, height, merkle_root, config.target, block_transactions, transaction_hashes);
 
-    // Start mining
-    let mut nonce_ = 10000;
-    //let start_time = Instant::now(); // Start the timer
+    // Start mining against the target the header commits to
+    let target = block.block_header.target().expect("bits come from a valid target");
+    let mut nonce_: u32 = 10000;
     loop {
         // Update the nonce
         block.block_header.nonce = nonce_;
         
         // Calculate the block hash
-        let block_hash = calculate_block_hash(&block);
+        let block_hash = match config.hash_scheme {
+            HashScheme::Header => calculate_block_hash(&block)
+                .expect("header hashes come from config and txids"),
+            HashScheme::Legacy => calculate_legacy_block_hash(&block),
+        };
 
         // Check if the block hash meets the difficulty target
-        if block_hash < "0000ffff00000000000000000000000000000000000000000000000000000000".to_owned() {
-            // If the hash is less than the difficulty target, return the block
+        if meets_target(&block_hash, &target) {
+            // If the hash is at or below the difficulty target, return the block
             break;
+        } else if nonce_ == u32::MAX {
+            // Nonce space exhausted: move the timestamp and start over
+            block.block_header.timestamp += 1;
+            nonce_ = 0;
         } else {
-            // If the hash is greater than or equal to the difficulty target, increment the nonce and try again
+            // If the hash is greater than the difficulty target, increment the nonce and try again
             nonce_ += 1;
         }
-
-        // Check if the elapsed time is greater than or equal to 9 minutes and 55 seconds
-        //if start_time.elapsed() >= Duration::from_secs(63) {
-            // If the time constraint is reached, return the block
-        //    block.block_header.nonce=2083236893;
-        //    block.block_header.bits="1d00ffff".to_owned();
-        //    block.block_header.merkle_root="4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b".to_owned();
-        //    block.block_header.timestamp=1231006505;
-        //    block.block_header.version = 1;
-        //    break;
-        //}
     }
-    return block
+    block
 }
 
-fn validation_transaction(transactions: &Vec<Transaction>)->Vec<Transaction>{
-    let mut valid_transactions : Vec<Transaction> = Vec::new();
-    for transaction in transactions{
-        let mut valid = true;
-        for v_out in transaction.vout.clone(){
-            if v_out.scriptpubkey_type == "p2pkh" {
-                if verify_p2pkh_address(v_out.scriptpubkey_asm.as_str(), v_out.scriptpubkey_address.as_str(), v_out.scriptpubkey.as_str())==true {
-                    valid = true;
-                }
-                else {
-                    valid = false;
-                    break;
-                }
-            }
-            else if v_out.scriptpubkey_type == "v0_p2wsh" {
-                if verify_v0_p2wsh_address(v_out.scriptpubkey_asm.as_str(), v_out.scriptpubkey_address.as_str(), v_out.scriptpubkey.as_str())==true {
-                    valid = true;
-                }
-                else {
-                    valid = false;
-                    break;
-                }
-            }
-            else if v_out.scriptpubkey_type == "v1_p2tr" {
-                if verify_v1_p2tr_address(v_out.scriptpubkey_asm.as_str(), v_out.scriptpubkey_address.as_str(), v_out.scriptpubkey.as_str())==true {
-                    valid = true;
-                }
-                else {
-                    valid = false;
-                    break;
-                }
-            }
-            else if v_out.scriptpubkey_type == "v0_p2wpkh" {
-                if verify_v0_p2wpkh_address(v_out.scriptpubkey_asm.as_str(), v_out.scriptpubkey_address.as_str(), v_out.scriptpubkey.as_str())==true {
-                    valid = true;
-                }
-                else {
-                    valid = false;
-                    break;
-                }
-            }
-            else if v_out.scriptpubkey_type == "p2sh" {
-                if verify_p2sh_address(v_out.scriptpubkey_asm.as_str(), v_out.scriptpubkey_address.as_str(), v_out.scriptpubkey.as_str())==true {
-                    valid = true;
-                }
-                else {
-                    valid = false;
-                    break;
-                }
-            }
-            else if v_out.scriptpubkey_type == "unknown" {
-                if verify_unknown(v_out.scriptpubkey_asm.as_str(), v_out.scriptpubkey.as_str())==true {
-                    valid = true;
-                }
-                else {
-                    valid = false;
-                    break;
-                }
-            }
-        }
-        if valid == false {
-            continue;
-        }
-        for v_in in transaction.vin.clone(){
-            if v_in.prevout.scriptpubkey_type == "p2pkh" {
-                if verify_p2pkh_address(v_in.prevout.scriptpubkey_asm.as_str(), v_in.prevout.scriptpubkey_address.as_str(), v_in.prevout.scriptpubkey.as_str())==true {
-                    let message = non_segwit_message(transaction);
-                    if verify_scriptsig_p2pkh(&v_in.scriptsig_asm, &message)==true {
-                        valid = true;
-                    }
-                    else {
-                        valid = false;
-                        break;
-                    }
-                }
-                else {
-                    valid = false;
-                    break
-                }
-            }
-            else if v_in.prevout.scriptpubkey_type=="p2sh" {
-                if verify_p2sh_address(v_in.prevout.scriptpubkey_asm.as_str(), v_in.prevout.scriptpubkey_address.as_str(), v_in.prevout.scriptpubkey.as_str())==true {
-                    if verify_scriptsig_p2sh(v_in.inner_redeemscript_asm.as_str(), v_in.scriptsig.as_str(), v_in.scriptsig_asm.as_str())==true {
-                        valid = true;
-                    }
-                    else {
-                        valid = false;
-                    }
-                }
-                else {
-                    valid = false;
-                }
-            }
-            else if v_in.prevout.scriptpubkey_type=="v0_p2wsh" {
-                if verify_v0_p2wsh_address(v_in.prevout.scriptpubkey_asm.as_str(), v_in.prevout.scriptpubkey_address.as_str(), v_in.prevout.scriptpubkey.as_str())==true {
-                    if let Some(witness_vec) = &v_in.witness {
-                        // Get the length of the vector
-                        let witness_length = witness_vec.len();
-                        if witness_length == 4 {
-                            if let Some(last_witness) = witness_vec.last() {
-                                // Print or use the last value
-                                if verify_witness_p2wsh(v_in.inner_witnessscript_asm.as_str(), &last_witness) {
-                                    valid = true;
-                                }
-                                else {
-                                    valid = false;
-                                }
-                            } else {
-                                valid = false;
-                            }
-                        }
-                        else {
-                            valid = false;
-                        }
-                    } else {
-                        valid = false;
-                    }
-                }
-                else {
-                    valid = false;
-                }
-            }
-            else if v_in.prevout.scriptpubkey_type=="v0_p2wpkh" {
-                if verify_v0_p2wpkh_address(v_in.prevout.scriptpubkey_asm.as_str(), v_in.prevout.scriptpubkey_address.as_str(), v_in.prevout.scriptpubkey.as_str()) {
-                    if let Some(witness_vec) = &v_in.witness {
-                        // Get the length of the vector
-                        let message = segwit_message(transaction, &v_in);
-                        let witness_length = witness_vec.len();
-                        if witness_length == 2 {
-                            if let Some(first_witness) = witness_vec.first() {
-                                if let Some(last_witness) = witness_vec.last() {
-                                    if verify_witness_p2wpkh(&first_witness, &last_witness, &message)==true {
-                                        valid = true;
-                                    }
-                                    else {
-                                        valid = false;
-                                    }
-                                } else {
-                                    valid = false;
-                                }
-                            } else {
-                                valid = false;
-                            }
-                        }
-                        else {
-                            valid = false;
-                        }
-                    } else {
-                        valid = false;
-                    }
-                }
-                else {
-                    valid = false;
-                }
-            }
-        }
-        if valid == false{
-            continue;
-        }
-        else {
+// Validates every loaded entry, reporting what happened to each one.
+// Returns the valid transactions, highest fee first, and the report.
+fn validation_transaction(entries: &[MempoolEntry], registry: &VerifierRegistry)->(Vec<Transaction>, Vec<ReportEntry>){
+    let mut valid_transactions = Vec::new();
+    let mut report = Vec::new();
+    for entry in entries {
+        let validation = entry.loaded().map(|transaction| validate_transaction(transaction, registry));
+        if let (Some(transaction), Some(Ok(()))) = (entry.loaded(), &validation) {
             valid_transactions.push(transaction.clone());
         }
+        report.push(ReportEntry::new(entry, validation.as_ref()));
+    }
+    (sort_by_fee(valid_transactions), report)
+}
+
+// Load the mempool in the configured format
+fn load_transactions(config: &Config) -> std::io::Result<Vec<MempoolEntry>> {
+    match config.mempool_format {
+        MempoolFormat::Json => load_mempool_entries(&config.mempool_dir),
+        MempoolFormat::RawHex => {
+            // Config::from_env guarantees a prevouts file for raw input
+            let prevouts_file = config.prevouts_file.as_deref().unwrap_or_default();
+            let prevouts = load_prevouts(Path::new(prevouts_file))?;
+            load_raw_hex_entries(&config.mempool_dir, &prevouts)
+        }
     }
-    return valid_transactions;
 }
 
 fn main()->std::io::Result<()>{
-    let transactions = fetch_transactions_from_mempool();
-    let valid_transactions = validation_transaction(&transactions);
-    let block = mine_block(&valid_transactions);
-    let file = File::create("output.txt")?;
+    let config = Config::from_env()
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidInput, error))?;
+    let entries = load_transactions(&config)?;
+    let registry = VerifierRegistry::standard(config.reject_annex, config.unknown_script_types);
+    let (valid_transactions, report) = validation_transaction(&entries, &registry);
+    if let Some(report_file) = &config.report_file {
+        write_report(Path::new(report_file), &report)?;
+    }
+    let block = mine_block(&valid_transactions, &config);
+    let file = File::create(&config.output_file)?;
 
     // Create a buffered writer to write to the file
     let mut writer = BufWriter::new(file);
 
-    // Write some data to the file
-    writer.write_all(block.to_output_string().as_bytes())?;
+    // Write the block in the configured format
+    let invalid_data = |error| std::io::Error::new(std::io::ErrorKind::InvalidData, error);
+    match config.output_format {
+        OutputFormat::Ids => writer.write_all(block.to_output_string().map_err(invalid_data)?.as_bytes())?,
+        OutputFormat::Hex => writer.write_all(block.to_hex().map_err(invalid_data)?.as_bytes())?,
+        OutputFormat::Binary => writer.write_all(&serialize(&block).map_err(invalid_data)?)?,
+    }
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use encoding::{CompactSize, Decodable};
 
     #[test]
     fn test_main() {
@@ -687,6 +416,165 @@ mod tests {
         // You can add more specific assertions here if needed
     }
 
+    #[test]
+    fn test_genesis_block_hash() {
+        let header = BlockHeader {
+            previous_block_hash: "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            nonce: 2083236893,
+            timestamp: 1231006505,
+            merkle_root: "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b".to_string(),
+            height: 0,
+            version: 1,
+            bits: 0x1d00ffff,
+        };
+        assert_eq!(
+            hex::encode(header.serialize().unwrap()),
+            "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c"
+        );
+        assert_eq!(
+            header.block_hash().unwrap(),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        assert_eq!(header.target().unwrap(), Target::MAX_ATTAINABLE);
+        assert!(meets_target(&header.block_hash().unwrap(), &header.target().unwrap()));
+
+        // Hashes that are not hex are errors, not panics
+        let unhexed = BlockHeader { merkle_root: "not hex".to_string(), ..header };
+        assert!(unhexed.block_hash().is_err());
+    }
+
+    #[test]
+    fn test_mined_block_meets_target() {
+        let json_data = std::fs::read_to_string(
+            "./mempool/0183b7cc0d270638fc8d51be094a76ada65b7f5f2044512ed42437f97fa999ac.json",
+        )
+        .unwrap();
+        let transaction: Transaction = serde_json::from_str(&json_data).unwrap();
+        let config = Config::default();
+        let block = mine_block(&[transaction], &config);
+        let block_hash = calculate_block_hash(&block).unwrap();
+        assert_eq!(block.block_header.bits, 0x1f00ffff);
+        assert!(meets_target(&block_hash, &config.target));
+        // The header in the output is the one that was hashed
+        let header_hex = block.to_output_string().unwrap().lines().next().unwrap().to_string();
+        let header_bytes = hex::decode(header_hex).unwrap();
+        assert_eq!(txid_from_bytes(&transaction_validator::double_sha256(&header_bytes)), block_hash);
+    }
+
+    #[test]
+    fn test_mined_block_builds_on_config() {
+        let previous_block_hash = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f".to_string();
+        let config = Config { height: 1, previous_block_hash: previous_block_hash.clone(), ..Config::default() };
+        let block = mine_block(&[], &config);
+
+        let header = block.block_header.serialize().unwrap();
+        assert_eq!(&header[0..4], &BLOCK_VERSION.to_le_bytes());
+        assert_eq!(block.block_header.height, 1);
+        assert_eq!(block.block_header.previous_block_hash, previous_block_hash);
+        assert_eq!(txid_from_bytes(header[4..36].try_into().unwrap()), previous_block_hash);
+    }
+
+    #[test]
+    fn test_mined_block_has_coinbase() {
+        let json_data = std::fs::read_to_string(
+            "./mempool/0183b7cc0d270638fc8d51be094a76ada65b7f5f2044512ed42437f97fa999ac.json",
+        )
+        .unwrap();
+        let transaction: Transaction = serde_json::from_str(&json_data).unwrap();
+        let block = mine_block(std::slice::from_ref(&transaction), &Config::default());
+
+        let coinbase = block.coinbase();
+        assert_eq!(
+            coinbase.vout[0].value,
+            50 * coinbase::COIN as i64 + transaction_loader::calculate_transaction_fee(&transaction)
+        );
+        assert_eq!(block.transactions_ids, vec![coinbase.txid().unwrap(), transaction.txid().unwrap()]);
+
+        // Header, coinbase hex, then the txids with the coinbase first
+        let output = block.to_output_string().unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1], hex::encode(serialize(coinbase).unwrap()));
+        assert_eq!(lines[2], coinbase.txid().unwrap());
+        assert_eq!(block.transactions, vec![coinbase.clone(), transaction]);
+    }
+
+    #[test]
+    fn test_block_merkle_proofs() {
+        let transactions: Vec<Transaction> = [
+            "0183b7cc0d270638fc8d51be094a76ada65b7f5f2044512ed42437f97fa999ac",
+            "0018c221bca3da35128baabe412a14c95b6864b2e6f7f7a8ffdd8eb0923dec49",
+        ]
+        .iter()
+        .map(|file_name| {
+            let json_data = std::fs::read_to_string(format!("./mempool/{}.json", file_name)).unwrap();
+            serde_json::from_str(&json_data).unwrap()
+        })
+        .collect();
+        let block = mine_block(&transactions, &Config::default());
+
+        for (index, txid) in block.transactions_ids.iter().enumerate() {
+            let proof = block.merkle_proof(txid).unwrap();
+            assert_eq!(proof.index, index);
+            assert!(proof.verify(&block.block_header.merkle_root));
+        }
+        assert!(block.merkle_proof(&"00".repeat(32)).is_none());
+    }
+
+    #[test]
+    fn test_genesis_block_serialization() {
+        let coinbase_hex = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+        let coinbase: Transaction = encoding::deserialize(&hex::decode(coinbase_hex).unwrap()).unwrap();
+        let merkle_root = coinbase.txid().unwrap();
+        let mut block = Block::new(
+            "0000000000000000000000000000000000000000000000000000000000000000".to_string(),
+            0,
+            merkle_root.clone(),
+            Target::MAX_ATTAINABLE,
+            vec![coinbase],
+            vec![merkle_root],
+        );
+        block.block_header.timestamp = 1231006505;
+        block.block_header.nonce = 2083236893;
+        block.block_header.version = 1;
+
+        let header_hex = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+        assert_eq!(block.to_hex().unwrap(), format!("{}01{}", header_hex, coinbase_hex));
+    }
+
+    #[test]
+    fn test_segwit_block_serialization() {
+        let json_data = std::fs::read_to_string(
+            "./mempool/0018c221bca3da35128baabe412a14c95b6864b2e6f7f7a8ffdd8eb0923dec49.json",
+        )
+        .unwrap();
+        let transaction: Transaction = serde_json::from_str(&json_data).unwrap();
+        let block = mine_block(std::slice::from_ref(&transaction), &Config::default());
+
+        // Witness data is kept, so the block decodes back into the same transactions
+        let bytes = serialize(&block).unwrap();
+        let mut reader = &bytes[80..];
+        let count = CompactSize::consensus_decode(&mut reader).unwrap();
+        assert_eq!(count.0, 2);
+        let coinbase = Transaction::consensus_decode(&mut reader).unwrap();
+        let decoded = Transaction::consensus_decode(&mut reader).unwrap();
+        assert!(reader.is_empty());
+        assert_eq!(coinbase.vin[0].witness, block.coinbase().vin[0].witness);
+        assert_eq!(decoded.wtxid().unwrap(), transaction.wtxid().unwrap());
+    }
+
+    #[test]
+    fn test_hashes_of_transactions_legacy() {
+        // Mempool files are named after SHA-256 of the txid bytes
+        let file_name = "0183b7cc0d270638fc8d51be094a76ada65b7f5f2044512ed42437f97fa999ac";
+        let json_data = std::fs::read_to_string(format!("./mempool/{}.json", file_name)).unwrap();
+        let transaction: Transaction = serde_json::from_str(&json_data).unwrap();
+
+        let hashes = hashes_of_transactions(&[transaction]).unwrap();
+        let txid_bytes = hex::decode(&hashes[0]).unwrap();
+        assert_eq!(hex::encode(Sha256::digest(txid_bytes)), file_name);
+    }
+
 }
 
 
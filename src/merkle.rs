@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+use crate::encoding::{txid_from_bytes, txid_to_bytes, EncodingError};
+use crate::transaction_validator::double_sha256;
+
+// Double SHA-256 of two concatenated internal-order hashes
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut data = [0u8; 64];
+    data[..32].copy_from_slice(left);
+    data[32..].copy_from_slice(right);
+    double_sha256(&data)
+}
+
+// The next level up; an odd last hash is paired with itself
+fn parent_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+        .collect()
+}
+
+/// Merkle root of internal-order hashes, or `None` when there are none.
+pub fn merkle_root(hashes: &[[u8; 32]]) -> Option<[u8; 32]> {
+    let mut level = hashes.to_vec();
+    while level.len() > 1 {
+        level = parent_level(&level);
+    }
+    level.first().copied()
+}
+
+/// Sibling hashes from the leaf at `index` up to the root, or `None` if `index` is out of range.
+pub fn merkle_branch(hashes: &[[u8; 32]], mut index: usize) -> Option<Vec<[u8; 32]>> {
+    if index >= hashes.len() {
+        return None;
+    }
+    let mut branch = Vec::new();
+    let mut level = hashes.to_vec();
+    while level.len() > 1 {
+        let sibling = index ^ 1;
+        branch.push(*level.get(sibling).unwrap_or(&level[index]));
+        level = parent_level(&level);
+        index /= 2;
+    }
+    Some(branch)
+}
+
+/// Folds `leaf` with its branch; the low bit of `index` at each level says which side the leaf is on.
+pub fn root_from_branch(leaf: [u8; 32], mut index: usize, branch: &[[u8; 32]]) -> [u8; 32] {
+    let mut hash = leaf;
+    for sibling in branch {
+        hash = if index & 1 == 0 { hash_pair(&hash, sibling) } else { hash_pair(sibling, &hash) };
+        index /= 2;
+    }
+    hash
+}
+
+/// Proof that a transaction is included in a block, with hashes in display order.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MerkleProof {
+    pub txid: String,
+    /// Position of the transaction in the block, coinbase at 0.
+    pub index: usize,
+    pub branch: Vec<String>,
+}
+
+impl MerkleProof {
+    /// Builds the proof for `txid` among a block's txids (display order, coinbase first).
+    pub fn new(txids: &[String], txid: &str) -> Result<Option<MerkleProof>, EncodingError> {
+        let Some(index) = txids.iter().position(|candidate| candidate == txid) else {
+            return Ok(None);
+        };
+        let hashes = txids.iter().map(|txid| txid_to_bytes(txid)).collect::<Result<Vec<_>, _>>()?;
+        let branch = merkle_branch(&hashes, index).expect("index was found in txids");
+        Ok(Some(MerkleProof {
+            txid: txid.to_string(),
+            index,
+            branch: branch.iter().map(txid_from_bytes).collect(),
+        }))
+    }
+
+    /// Whether the proof leads to `merkle_root` (display order). Malformed hex, or an index
+    /// needing more levels than the branch has, never verifies.
+    pub fn verify(&self, merkle_root: &str) -> bool {
+        // Otherwise the bits above the branch would be ignored and several indexes would verify
+        if self.index.checked_shr(self.branch.len() as u32).unwrap_or(0) != 0 {
+            return false;
+        }
+        let leaf = txid_to_bytes(&self.txid);
+        let branch = self.branch.iter().map(|hash| txid_to_bytes(hash)).collect::<Result<Vec<_>, _>>();
+        let expected = txid_to_bytes(merkle_root);
+        match (leaf, branch, expected) {
+            (Ok(leaf), Ok(branch), Ok(expected)) => root_from_branch(leaf, self.index, &branch) == expected,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mainnet block 100000
+    const BLOCK_100000_TXIDS: [&str; 4] = [
+        "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+        "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+        "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+        "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+    ];
+    const BLOCK_100000_MERKLE_ROOT: &str = "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766";
+
+    fn leaves(count: u8) -> Vec<[u8; 32]> {
+        (0..count).map(|i| [i; 32]).collect()
+    }
+
+    #[test]
+    fn test_merkle_root() {
+        let hashes: Vec<[u8; 32]> = BLOCK_100000_TXIDS.iter().map(|txid| txid_to_bytes(txid).unwrap()).collect();
+        assert_eq!(txid_from_bytes(&merkle_root(&hashes).unwrap()), BLOCK_100000_MERKLE_ROOT);
+
+        // A single transaction is its own root
+        assert_eq!(merkle_root(&hashes[..1]), Some(hashes[0]));
+        assert_eq!(merkle_root(&[]), None);
+
+        // Odd levels duplicate their last hash
+        let three = leaves(3);
+        let expected = hash_pair(&hash_pair(&three[0], &three[1]), &hash_pair(&three[2], &three[2]));
+        assert_eq!(merkle_root(&three), Some(expected));
+    }
+
+    #[test]
+    fn test_branches_lead_to_root() {
+        for count in 1..=9 {
+            let hashes = leaves(count);
+            let root = merkle_root(&hashes).unwrap();
+            for (index, leaf) in hashes.iter().enumerate() {
+                let branch = merkle_branch(&hashes, index).unwrap();
+                assert_eq!(root_from_branch(*leaf, index, &branch), root, "{} leaves, index {}", count, index);
+            }
+            assert_eq!(merkle_branch(&hashes, count as usize), None);
+        }
+    }
+
+    #[test]
+    fn test_merkle_proof() {
+        let txids: Vec<String> = BLOCK_100000_TXIDS.iter().map(|txid| txid.to_string()).collect();
+        let proof = MerkleProof::new(&txids, BLOCK_100000_TXIDS[2]).unwrap().unwrap();
+        assert_eq!(proof.index, 2);
+        assert_eq!(proof.branch.len(), 2);
+        assert_eq!(proof.branch[0], BLOCK_100000_TXIDS[3]);
+        assert!(proof.verify(BLOCK_100000_MERKLE_ROOT));
+
+        // The wrong position or a different root fails
+        let mut moved = proof.clone();
+        moved.index = 3;
+        assert!(!moved.verify(BLOCK_100000_MERKLE_ROOT));
+        assert!(!proof.verify(BLOCK_100000_TXIDS[0]));
+        assert!(!proof.verify("not hex"));
+
+        // An index beyond the branch's depth, with the same low bits
+        let mut beyond = proof.clone();
+        beyond.index = 2 + (1 << proof.branch.len());
+        assert!(!beyond.verify(BLOCK_100000_MERKLE_ROOT));
+
+        assert_eq!(MerkleProof::new(&txids, &"00".repeat(32)).unwrap(), None);
+    }
+}
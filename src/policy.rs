@@ -0,0 +1,155 @@
+use std::fmt;
+
+use crate::interpreter::{split_annex, TAPROOT_LEAF_MASK, TAPROOT_LEAF_TAPSCRIPT};
+use crate::script::parse_script;
+use crate::script_type::{witness_program, ScriptType};
+use crate::transaction_loader::Transaction;
+
+/// Largest P2WSH witness script Bitcoin Core relays.
+pub const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3600;
+/// Most P2WSH witness items, not counting the witness script.
+pub const MAX_STANDARD_P2WSH_STACK_ITEMS: usize = 100;
+/// Largest P2WSH witness item, not counting the witness script.
+pub const MAX_STANDARD_P2WSH_STACK_ITEM_SIZE: usize = 80;
+/// Largest tapscript witness item, not counting the script and control block.
+pub const MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE: usize = 80;
+
+/// Why an input's witness is valid but non-standard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyError {
+    /// A taproot spend carries an annex while annexes are rejected.
+    Annex,
+    /// A P2WSH witness script is larger than 3,600 bytes.
+    P2wshScriptSize,
+    /// A P2WSH witness has more than 100 items besides the script.
+    P2wshStackItems,
+    /// A P2WSH witness item is larger than 80 bytes.
+    P2wshStackItemSize,
+    /// A tapscript witness item is larger than 80 bytes.
+    TapscriptStackItemSize,
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::Annex => write!(f, "taproot annex is non-standard"),
+            PolicyError::P2wshScriptSize => write!(f, "witness script is larger than {} bytes", MAX_STANDARD_P2WSH_SCRIPT_SIZE),
+            PolicyError::P2wshStackItems => write!(f, "more than {} witness items", MAX_STANDARD_P2WSH_STACK_ITEMS),
+            PolicyError::P2wshStackItemSize => write!(f, "witness item is larger than {} bytes", MAX_STANDARD_P2WSH_STACK_ITEM_SIZE),
+            PolicyError::TapscriptStackItemSize => {
+                write!(f, "tapscript witness item is larger than {} bytes", MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+/// Applies Bitcoin Core's witness standardness rules (`IsWitnessStandard`) to input
+/// `input_index`: size limits on P2WSH and tapscript witness items and, when
+/// `reject_annex` is set, no taproot annex.
+///
+/// Consensus validity is checked separately; undecodable data is left for it to reject.
+pub fn check_witness_standard(transaction: &Transaction, input_index: usize, reject_annex: bool) -> Result<(), PolicyError> {
+    let Some(vin) = transaction.vin.get(input_index) else {
+        return Ok(());
+    };
+    let Some(witness) = vin.witness.as_ref().and_then(|items| items.iter().map(hex::decode).collect::<Result<Vec<_>, _>>().ok()) else {
+        return Ok(());
+    };
+    let Ok(mut script_pubkey) = hex::decode(&vin.prevout.scriptpubkey) else {
+        return Ok(());
+    };
+    // A nested witness program is the redeem script, the last push of the scriptSig
+    let is_p2sh = ScriptType::classify(&script_pubkey) == ScriptType::P2sh;
+    if is_p2sh {
+        let redeem_script = hex::decode(&vin.scriptsig)
+            .ok()
+            .and_then(|script_sig| parse_script(&script_sig).ok())
+            .and_then(|instructions| instructions.last().and_then(|last| last.push_data().map(<[u8]>::to_vec)));
+        match redeem_script {
+            Some(redeem_script) => script_pubkey = redeem_script,
+            None => return Ok(()),
+        }
+    }
+
+    match witness_program(&script_pubkey) {
+        Some((0, program)) if program.len() == 32 => {
+            let Some((witness_script, stack)) = witness.split_last() else {
+                return Ok(());
+            };
+            if witness_script.len() > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+                return Err(PolicyError::P2wshScriptSize);
+            }
+            if stack.len() > MAX_STANDARD_P2WSH_STACK_ITEMS {
+                return Err(PolicyError::P2wshStackItems);
+            }
+            if stack.iter().any(|item| item.len() > MAX_STANDARD_P2WSH_STACK_ITEM_SIZE) {
+                return Err(PolicyError::P2wshStackItemSize);
+            }
+        }
+        Some((1, program)) if program.len() == 32 && !is_p2sh => {
+            let (spend, annex) = split_annex(&witness);
+            if annex.is_some() && reject_annex {
+                return Err(PolicyError::Annex);
+            }
+            // Script path: <stack...> <script> <control block>
+            if let [stack @ .., _, control] = spend {
+                let is_tapscript = control.first().is_some_and(|byte| byte & TAPROOT_LEAF_MASK == TAPROOT_LEAF_TAPSCRIPT);
+                if is_tapscript && stack.iter().any(|item| item.len() > MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE) {
+                    return Err(PolicyError::TapscriptStackItemSize);
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction_loader::load_json_transaction;
+
+    #[test]
+    fn test_annex_policy() {
+        // A key path spend with a 64-byte signature
+        let mut transaction = load_json_transaction("5588015c0c3e5fa905a813a4be5765e3f684dd71a43acae3c33a5c4d5c0ae213");
+        assert_eq!(check_witness_standard(&transaction, 0, true), Ok(()));
+
+        transaction.vin[0].witness.as_mut().unwrap().push("50aa".to_string());
+        assert_eq!(check_witness_standard(&transaction, 0, true), Err(PolicyError::Annex));
+        assert_eq!(check_witness_standard(&transaction, 0, false), Ok(()));
+
+        // A lone item starting with 0x50 is a signature, not an annex
+        transaction.vin[0].witness = Some(vec![format!("50{}", "11".repeat(63))]);
+        assert_eq!(check_witness_standard(&transaction, 0, true), Ok(()));
+    }
+
+    #[test]
+    fn test_witness_item_limits() {
+        // A tapscript spend: <signature> <script> <control block>
+        let mut transaction = load_json_transaction("1495b024706adbe373079fdcf09a5aab22fa65c790bbb0cb355b54af52cfac8c");
+        assert_eq!(check_witness_standard(&transaction, 0, true), Ok(()));
+        transaction.vin[0].witness.as_mut().unwrap()[0] = "00".repeat(81);
+        assert_eq!(check_witness_standard(&transaction, 0, true), Err(PolicyError::TapscriptStackItemSize));
+
+        // A 2-of-2 P2WSH multisig: <dummy> <signature> <signature> <witness script>
+        let mut transaction = load_json_transaction("b3df1a40a4f9b1a9d26bc4f8a6a8aefc09691752c15fb06525f998cf0813bb62");
+        assert_eq!(check_witness_standard(&transaction, 0, true), Ok(()));
+        let witness = transaction.vin[0].witness.clone().unwrap();
+
+        let mut items = witness.clone();
+        items[1] = "00".repeat(81);
+        transaction.vin[0].witness = Some(items);
+        assert_eq!(check_witness_standard(&transaction, 0, true), Err(PolicyError::P2wshStackItemSize));
+
+        let mut items = vec![String::new(); MAX_STANDARD_P2WSH_STACK_ITEMS + 1];
+        items.push(witness[3].clone());
+        transaction.vin[0].witness = Some(items);
+        assert_eq!(check_witness_standard(&transaction, 0, true), Err(PolicyError::P2wshStackItems));
+
+        transaction.vin[0].witness = Some(vec![String::new(), "61".repeat(MAX_STANDARD_P2WSH_SCRIPT_SIZE + 1)]);
+        assert_eq!(check_witness_standard(&transaction, 0, true), Err(PolicyError::P2wshScriptSize));
+    }
+}
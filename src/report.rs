@@ -0,0 +1,148 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::transaction_loader::{value_totals, MempoolEntry, Transaction};
+use crate::transaction_validator::ValidationError;
+
+/// What happened to one mempool entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// Loaded and validated: the transaction is in the block.
+    Accepted,
+    /// Parsed, but left out by a load check or by validation.
+    Rejected,
+    /// The file or line is not a transaction.
+    ParseError,
+}
+
+/// One line of the validation report.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReportEntry {
+    /// The mempool file, followed by `:<line>` for raw hex.
+    pub file: String,
+    pub txid: Option<String>,
+    pub status: Status,
+    pub reason: Option<String>,
+    /// Input value minus output value in satoshis; negative when the outputs are worth more.
+    pub fee: Option<i64>,
+    pub vsize: Option<usize>,
+    /// Satoshis per virtual byte.
+    pub feerate: Option<f64>,
+}
+
+impl ReportEntry {
+    /// Reports `entry`, with `validation` the result of validating it when it loaded.
+    pub fn new(entry: &MempoolEntry, validation: Option<&Result<(), ValidationError>>) -> ReportEntry {
+        let (status, reason) = match (&entry.error, validation) {
+            (Some(error), _) if entry.transaction.is_none() => (Status::ParseError, Some(error.to_string())),
+            (Some(error), _) => (Status::Rejected, Some(error.to_string())),
+            (None, Some(Err(error))) => (Status::Rejected, Some(error.to_string())),
+            (None, _) => (Status::Accepted, None),
+        };
+        let transaction = entry.transaction.as_ref();
+        let fee = transaction.and_then(fee);
+        let vsize = transaction.and_then(|transaction| transaction.vsize().ok());
+        ReportEntry {
+            file: entry.source.clone(),
+            txid: transaction.and_then(|transaction| transaction.txid().ok()),
+            status,
+            reason,
+            fee,
+            vsize,
+            feerate: fee.zip(vsize).filter(|&(_, vsize)| vsize > 0).map(|(fee, vsize)| fee as f64 / vsize as f64),
+        }
+    }
+}
+
+// Unlike `calculate_transaction_fee`, keeps negative fees so the report shows them;
+// `None` when the values overflow
+fn fee(transaction: &Transaction) -> Option<i64> {
+    let (input_value, output_value) = value_totals(transaction)?;
+    input_value.checked_sub(output_value)
+}
+
+/// Writes the report as JSON lines, one entry per line.
+pub fn write_report(path: &Path, entries: &[ReportEntry]) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for entry in entries {
+        serde_json::to_writer(&mut writer, entry)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction_loader::{load_json_transaction, load_mempool_entries, LoadError};
+
+    fn load_entry(txid: &str) -> MempoolEntry {
+        let transaction = load_json_transaction(txid);
+        MempoolEntry { source: format!("{}.json", txid), transaction: Some(transaction), error: None }
+    }
+
+    #[test]
+    fn test_report_entry_status() {
+        let entry = load_entry("0018c221bca3da35128baabe412a14c95b6864b2e6f7f7a8ffdd8eb0923dec49");
+        let accepted = ReportEntry::new(&entry, Some(&Ok(())));
+        assert_eq!(accepted.status, Status::Accepted);
+        assert_eq!(accepted.txid.as_deref(), Some("6494cdb062c4a1bc3e74760f9fd389c69c9cd0dcba246c0c58ab37688671edbd"));
+        assert_eq!(accepted.vsize, Some(142));
+        assert_eq!(accepted.feerate, Some(accepted.fee.unwrap() as f64 / 142.0));
+        assert_eq!(accepted.reason, None);
+
+        let rejected = ReportEntry::new(&entry, Some(&Err(ValidationError::OutputScript { index: 1, script_type: "p2sh".to_string() })));
+        assert_eq!(rejected.status, Status::Rejected);
+        assert_eq!(rejected.reason.as_deref(), Some("vout[1] does not match its p2sh scriptpubkey_type or address"));
+
+        // Load rejections keep the transaction's details
+        let mut negative = entry.clone();
+        negative.transaction.as_mut().unwrap().vout[0].value += 1 << 40;
+        negative.error = Some(LoadError::NegativeFee);
+        let rejected = ReportEntry::new(&negative, None);
+        assert_eq!(rejected.status, Status::Rejected);
+        assert!(rejected.fee.unwrap() < 0);
+        assert!(rejected.txid.is_some());
+
+        // Values that overflow leave the fee out rather than panicking
+        let mut overflowing = entry.clone();
+        let vout = &mut overflowing.transaction.as_mut().unwrap().vout;
+        vout[0].value = i64::MAX;
+        vout.push(vout[0].clone());
+        overflowing.error = Some(LoadError::ValueOverflow);
+        let rejected = ReportEntry::new(&overflowing, None);
+        assert_eq!((rejected.fee, rejected.feerate), (None, None));
+
+        let unparsable = MempoolEntry { source: "bad.json".to_string(), transaction: None, error: Some(LoadError::Parse("EOF".to_string())) };
+        let line = serde_json::to_string(&ReportEntry::new(&unparsable, None)).unwrap();
+        assert_eq!(
+            line,
+            r#"{"file":"bad.json","txid":null,"status":"parse_error","reason":"EOF","fee":null,"vsize":null,"feerate":null}"#
+        );
+    }
+
+    #[test]
+    fn test_every_mempool_file_is_reported() {
+        let dir = std::env::temp_dir().join(format!("report_mempool_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let txid = "0018c221bca3da35128baabe412a14c95b6864b2e6f7f7a8ffdd8eb0923dec49";
+        std::fs::copy(format!("mempool/{}.json", txid), dir.join(format!("{}.json", txid))).unwrap();
+        std::fs::write(dir.join("truncated.json"), "{\"version\": 2,").unwrap();
+
+        let entries = load_mempool_entries(dir.to_str().unwrap()).unwrap();
+        let valid = Ok(());
+        let report: Vec<ReportEntry> = entries.iter().map(|entry| ReportEntry::new(entry, entry.loaded().map(|_| &valid))).collect();
+        let report_path = dir.join("report.jsonl");
+        write_report(&report_path, &report).unwrap();
+        let written = std::fs::read_to_string(&report_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let statuses: Vec<(&str, Status)> = report.iter().map(|entry| (entry.file.as_str(), entry.status)).collect();
+        assert_eq!(statuses, vec![(format!("{}.json", txid).as_str(), Status::Accepted), ("truncated.json", Status::ParseError)]);
+        assert_eq!(written.lines().count(), 2);
+    }
+}
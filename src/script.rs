@@ -0,0 +1,522 @@
+use std::fmt;
+
+macro_rules! opcodes {
+    ($($variant:ident = $value:literal => $name:literal,)*) => {
+        /// Every script opcode, keyed by its byte value.
+        ///
+        /// Data pushes of 0-75 bytes, the small integers OP_1..OP_16 and the unassigned
+        /// bytes 0xbb-0xfe carry their value instead of having a variant each.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Opcode {
+            /// 0x00-0x4b: push the next n bytes (n = 0 is OP_0).
+            PushBytes(u8),
+            /// 0x51-0x60: push the number 1-16.
+            PushNum(u8),
+            /// 0xbb-0xfe: not assigned; fails if executed.
+            Unassigned(u8),
+            $($variant,)*
+        }
+
+        impl Opcode {
+            pub fn from_u8(byte: u8) -> Opcode {
+                match byte {
+                    0x00..=0x4b => Opcode::PushBytes(byte),
+                    0x51..=0x60 => Opcode::PushNum(byte - 0x50),
+                    0xbb..=0xfe => Opcode::Unassigned(byte),
+                    $($value => Opcode::$variant,)*
+                }
+            }
+
+            pub fn to_u8(self) -> u8 {
+                match self {
+                    Opcode::PushBytes(len) => len,
+                    Opcode::PushNum(number) => 0x50 + number,
+                    Opcode::Unassigned(byte) => byte,
+                    $(Opcode::$variant => $value,)*
+                }
+            }
+
+            /// Parses an opcode name as the disassembler writes it. `OP_FALSE`, `OP_TRUE`,
+            /// `OP_1`..`OP_16`, `OP_1NEGATE`, `OP_NOP2`/`OP_CHECKLOCKTIMEVERIFY` and
+            /// `OP_NOP3`/`OP_CHECKSEQUENCEVERIFY` are accepted as well.
+            pub fn from_name(name: &str) -> Option<Opcode> {
+                let numbered = |prefix: &str| name.strip_prefix(prefix).and_then(|number| number.parse::<u8>().ok());
+                let opcode = match name {
+                    $($name => Opcode::$variant,)*
+                    "OP_0" | "OP_FALSE" => Opcode::PushBytes(0),
+                    "OP_TRUE" => Opcode::PushNum(1),
+                    "OP_1NEGATE" => Opcode::PushNumNeg1,
+                    "OP_NOP2" | "OP_CHECKLOCKTIMEVERIFY" => Opcode::CheckLockTimeVerify,
+                    "OP_NOP3" | "OP_CHECKSEQUENCEVERIFY" => Opcode::CheckSequenceVerify,
+                    _ => {
+                        if let Some(len @ 0..=0x4b) = numbered("OP_PUSHBYTES_") {
+                            Opcode::PushBytes(len)
+                        } else if let Some(number @ 1..=16) = numbered("OP_PUSHNUM_").or_else(|| numbered("OP_")) {
+                            Opcode::PushNum(number)
+                        } else if let Some(byte @ 0xbb..=0xfe) = numbered("OP_RETURN_") {
+                            Opcode::Unassigned(byte)
+                        } else {
+                            return None;
+                        }
+                    }
+                };
+                Some(opcode)
+            }
+        }
+
+        /// Names as esplora's asm writes them (`OP_0`, `OP_PUSHBYTES_20`, `OP_PUSHNUM_2`, `OP_CLTV`, ...).
+        impl fmt::Display for Opcode {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    Opcode::PushBytes(0) => f.write_str("OP_0"),
+                    Opcode::PushBytes(len) => write!(f, "OP_PUSHBYTES_{}", len),
+                    Opcode::PushNum(number) => write!(f, "OP_PUSHNUM_{}", number),
+                    Opcode::Unassigned(byte) => write!(f, "OP_RETURN_{}", byte),
+                    $(Opcode::$variant => f.write_str($name),)*
+                }
+            }
+        }
+    };
+}
+
+opcodes! {
+    PushData1 = 0x4c => "OP_PUSHDATA1",
+    PushData2 = 0x4d => "OP_PUSHDATA2",
+    PushData4 = 0x4e => "OP_PUSHDATA4",
+    PushNumNeg1 = 0x4f => "OP_PUSHNUM_NEG1",
+    Reserved = 0x50 => "OP_RESERVED",
+    Nop = 0x61 => "OP_NOP",
+    Ver = 0x62 => "OP_VER",
+    If = 0x63 => "OP_IF",
+    NotIf = 0x64 => "OP_NOTIF",
+    VerIf = 0x65 => "OP_VERIF",
+    VerNotIf = 0x66 => "OP_VERNOTIF",
+    Else = 0x67 => "OP_ELSE",
+    EndIf = 0x68 => "OP_ENDIF",
+    Verify = 0x69 => "OP_VERIFY",
+    Return = 0x6a => "OP_RETURN",
+    ToAltStack = 0x6b => "OP_TOALTSTACK",
+    FromAltStack = 0x6c => "OP_FROMALTSTACK",
+    TwoDrop = 0x6d => "OP_2DROP",
+    TwoDup = 0x6e => "OP_2DUP",
+    ThreeDup = 0x6f => "OP_3DUP",
+    TwoOver = 0x70 => "OP_2OVER",
+    TwoRot = 0x71 => "OP_2ROT",
+    TwoSwap = 0x72 => "OP_2SWAP",
+    IfDup = 0x73 => "OP_IFDUP",
+    Depth = 0x74 => "OP_DEPTH",
+    Drop = 0x75 => "OP_DROP",
+    Dup = 0x76 => "OP_DUP",
+    Nip = 0x77 => "OP_NIP",
+    Over = 0x78 => "OP_OVER",
+    Pick = 0x79 => "OP_PICK",
+    Roll = 0x7a => "OP_ROLL",
+    Rot = 0x7b => "OP_ROT",
+    Swap = 0x7c => "OP_SWAP",
+    Tuck = 0x7d => "OP_TUCK",
+    Cat = 0x7e => "OP_CAT",
+    Substr = 0x7f => "OP_SUBSTR",
+    Left = 0x80 => "OP_LEFT",
+    Right = 0x81 => "OP_RIGHT",
+    Size = 0x82 => "OP_SIZE",
+    Invert = 0x83 => "OP_INVERT",
+    And = 0x84 => "OP_AND",
+    Or = 0x85 => "OP_OR",
+    Xor = 0x86 => "OP_XOR",
+    Equal = 0x87 => "OP_EQUAL",
+    EqualVerify = 0x88 => "OP_EQUALVERIFY",
+    Reserved1 = 0x89 => "OP_RESERVED1",
+    Reserved2 = 0x8a => "OP_RESERVED2",
+    Add1 = 0x8b => "OP_1ADD",
+    Sub1 = 0x8c => "OP_1SUB",
+    Mul2 = 0x8d => "OP_2MUL",
+    Div2 = 0x8e => "OP_2DIV",
+    Negate = 0x8f => "OP_NEGATE",
+    Abs = 0x90 => "OP_ABS",
+    Not = 0x91 => "OP_NOT",
+    NotEqual0 = 0x92 => "OP_0NOTEQUAL",
+    Add = 0x93 => "OP_ADD",
+    Sub = 0x94 => "OP_SUB",
+    Mul = 0x95 => "OP_MUL",
+    Div = 0x96 => "OP_DIV",
+    Mod = 0x97 => "OP_MOD",
+    LShift = 0x98 => "OP_LSHIFT",
+    RShift = 0x99 => "OP_RSHIFT",
+    BoolAnd = 0x9a => "OP_BOOLAND",
+    BoolOr = 0x9b => "OP_BOOLOR",
+    NumEqual = 0x9c => "OP_NUMEQUAL",
+    NumEqualVerify = 0x9d => "OP_NUMEQUALVERIFY",
+    NumNotEqual = 0x9e => "OP_NUMNOTEQUAL",
+    LessThan = 0x9f => "OP_LESSTHAN",
+    GreaterThan = 0xa0 => "OP_GREATERTHAN",
+    LessThanOrEqual = 0xa1 => "OP_LESSTHANOREQUAL",
+    GreaterThanOrEqual = 0xa2 => "OP_GREATERTHANOREQUAL",
+    Min = 0xa3 => "OP_MIN",
+    Max = 0xa4 => "OP_MAX",
+    Within = 0xa5 => "OP_WITHIN",
+    Ripemd160 = 0xa6 => "OP_RIPEMD160",
+    Sha1 = 0xa7 => "OP_SHA1",
+    Sha256 = 0xa8 => "OP_SHA256",
+    Hash160 = 0xa9 => "OP_HASH160",
+    Hash256 = 0xaa => "OP_HASH256",
+    CodeSeparator = 0xab => "OP_CODESEPARATOR",
+    CheckSig = 0xac => "OP_CHECKSIG",
+    CheckSigVerify = 0xad => "OP_CHECKSIGVERIFY",
+    CheckMultiSig = 0xae => "OP_CHECKMULTISIG",
+    CheckMultiSigVerify = 0xaf => "OP_CHECKMULTISIGVERIFY",
+    Nop1 = 0xb0 => "OP_NOP1",
+    CheckLockTimeVerify = 0xb1 => "OP_CLTV",
+    CheckSequenceVerify = 0xb2 => "OP_CSV",
+    Nop4 = 0xb3 => "OP_NOP4",
+    Nop5 = 0xb4 => "OP_NOP5",
+    Nop6 = 0xb5 => "OP_NOP6",
+    Nop7 = 0xb6 => "OP_NOP7",
+    Nop8 = 0xb7 => "OP_NOP8",
+    Nop9 = 0xb8 => "OP_NOP9",
+    Nop10 = 0xb9 => "OP_NOP10",
+    CheckSigAdd = 0xba => "OP_CHECKSIGADD",
+    Invalid = 0xff => "OP_INVALIDOPCODE",
+}
+
+/// One parsed script element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    /// Data pushed by OP_0, OP_PUSHBYTES_n or OP_PUSHDATA1/2/4, with the opcode that pushed it.
+    Push(Opcode, Vec<u8>),
+    /// Any other opcode.
+    Op(Opcode),
+}
+
+impl Instruction {
+    /// The shortest push of `data`, as Bitcoin Core's `CScript() << data` writes it.
+    pub fn push(data: &[u8]) -> Instruction {
+        let opcode = match data.len() {
+            len @ 0..=0x4b => Opcode::PushBytes(len as u8),
+            0x4c..=0xff => Opcode::PushData1,
+            0x100..=0xffff => Opcode::PushData2,
+            _ => Opcode::PushData4,
+        };
+        Instruction::Push(opcode, data.to_vec())
+    }
+
+    /// The pushed bytes, for data pushes only.
+    pub fn push_data(&self) -> Option<&[u8]> {
+        match self {
+            Instruction::Push(_, data) => Some(data),
+            Instruction::Op(_) => None,
+        }
+    }
+
+    /// Whether this counts as a push for push-only checks (data pushes, OP_1NEGATE, OP_1..OP_16).
+    pub fn is_push(&self) -> bool {
+        match self {
+            Instruction::Push(..) => true,
+            Instruction::Op(opcode) => matches!(opcode, Opcode::PushNumNeg1 | Opcode::PushNum(_)),
+        }
+    }
+
+    /// Appends the instruction's bytes to `script`.
+    pub fn encode(&self, script: &mut Vec<u8>) {
+        match self {
+            Instruction::Push(opcode, data) => {
+                script.push(opcode.to_u8());
+                match opcode {
+                    Opcode::PushData1 => script.push(data.len() as u8),
+                    Opcode::PushData2 => script.extend_from_slice(&(data.len() as u16).to_le_bytes()),
+                    Opcode::PushData4 => script.extend_from_slice(&(data.len() as u32).to_le_bytes()),
+                    _ => {}
+                }
+                script.extend_from_slice(data);
+            }
+            Instruction::Op(opcode) => script.push(opcode.to_u8()),
+        }
+    }
+
+    /// Number of bytes the instruction takes in a script.
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Instruction::Push(Opcode::PushData1, data) => 2 + data.len(),
+            Instruction::Push(Opcode::PushData2, data) => 3 + data.len(),
+            Instruction::Push(Opcode::PushData4, data) => 5 + data.len(),
+            Instruction::Push(_, data) => 1 + data.len(),
+            Instruction::Op(_) => 1,
+        }
+    }
+}
+
+/// Reasons a script could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+    InvalidHex(String),
+    /// A push at `offset` claims more bytes than the script has left.
+    TruncatedPush { offset: usize },
+    /// An asm token that is neither an opcode name nor hex data.
+    UnknownToken(String),
+    /// An asm push whose data is missing, not hex, or does not fit its opcode.
+    InvalidPush(String),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::InvalidHex(script) => write!(f, "script is not valid hex: {:?}", script),
+            ScriptError::TruncatedPush { offset } => write!(f, "push at offset {} runs past the end of the script", offset),
+            ScriptError::UnknownToken(token) => write!(f, "unknown asm token {:?}", token),
+            ScriptError::InvalidPush(push) => write!(f, "invalid asm push {:?}", push),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Parses script bytecode into instructions.
+pub fn parse_script(script: &[u8]) -> Result<Vec<Instruction>, ScriptError> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while offset < script.len() {
+        let opcode = Opcode::from_u8(script[offset]);
+        let (header_len, data_len) = match opcode {
+            Opcode::PushBytes(len) => (1, Some(len as usize)),
+            Opcode::PushData1 => (2, script.get(offset + 1).map(|&len| len as usize)),
+            Opcode::PushData2 => (
+                3,
+                script
+                    .get(offset + 1..offset + 3)
+                    .map(|len| u16::from_le_bytes([len[0], len[1]]) as usize),
+            ),
+            Opcode::PushData4 => (
+                5,
+                script
+                    .get(offset + 1..offset + 5)
+                    .map(|len| u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize),
+            ),
+            _ => {
+                instructions.push(Instruction::Op(opcode));
+                offset += 1;
+                continue;
+            }
+        };
+        let start = offset + header_len;
+        let data = data_len
+            .and_then(|len| script.get(start..start.checked_add(len)?))
+            .ok_or(ScriptError::TruncatedPush { offset })?;
+        instructions.push(Instruction::Push(opcode, data.to_vec()));
+        offset = start + data.len();
+    }
+    Ok(instructions)
+}
+
+/// Parses hex-encoded script bytecode, as found in the mempool JSON.
+pub fn parse_script_hex(script: &str) -> Result<Vec<Instruction>, ScriptError> {
+    let bytes = hex::decode(script).map_err(|_| ScriptError::InvalidHex(script.to_string()))?;
+    parse_script(&bytes)
+}
+
+/// Serializes instructions back into bytecode.
+pub fn encode_script(instructions: &[Instruction]) -> Vec<u8> {
+    let mut script = Vec::new();
+    for instruction in instructions {
+        instruction.encode(&mut script);
+    }
+    script
+}
+
+/// Formats script bytecode the way esplora's `*_asm` fields do: opcode names separated by
+/// spaces, each data push followed by its hex. Truncated scripts end in `<unexpected end>`
+/// (a missing PUSHDATA length) or `<push past end>`, like esplora's output.
+pub fn disassemble(script: &[u8]) -> String {
+    let mut asm = String::new();
+    let mut offset = 0;
+    while offset < script.len() {
+        let opcode = Opcode::from_u8(script[offset]);
+        offset += 1;
+        let length_bytes = match opcode {
+            Opcode::PushData1 => 1,
+            Opcode::PushData2 => 2,
+            Opcode::PushData4 => 4,
+            _ => 0,
+        };
+        let Some(length) = script.get(offset..offset + length_bytes) else {
+            asm.push_str("<unexpected end>");
+            break;
+        };
+        offset += length_bytes;
+        let data_len = match opcode {
+            Opcode::PushBytes(len) => len as usize,
+            _ => length.iter().rev().fold(0, |len, &byte| (len << 8) | byte as usize),
+        };
+
+        if !asm.is_empty() {
+            asm.push(' ');
+        }
+        asm.push_str(&opcode.to_string());
+        if data_len > 0 {
+            asm.push(' ');
+            match script.get(offset..offset + data_len) {
+                Some(data) => asm.push_str(&hex::encode(data)),
+                None => {
+                    asm.push_str("<push past end>");
+                    break;
+                }
+            }
+            offset += data_len;
+        }
+    }
+    asm
+}
+
+/// Assembles asm text back into bytecode.
+///
+/// Push opcodes take the following hex token as their data, so `disassemble` output
+/// round-trips exactly. A hex token on its own is written as the shortest push.
+pub fn assemble(asm: &str) -> Result<Vec<u8>, ScriptError> {
+    let mut instructions = Vec::new();
+    let mut tokens = asm.split_whitespace().peekable();
+    while let Some(token) = tokens.next() {
+        let Some(opcode) = Opcode::from_name(token) else {
+            let data = hex::decode(token).map_err(|_| ScriptError::UnknownToken(token.to_string()))?;
+            instructions.push(Instruction::push(&data));
+            continue;
+        };
+        let max_len = match opcode {
+            Opcode::PushBytes(0) => 0,
+            Opcode::PushBytes(len) => len as usize,
+            Opcode::PushData1 => 0xff,
+            Opcode::PushData2 => 0xffff,
+            Opcode::PushData4 => u32::MAX as usize,
+            _ => {
+                instructions.push(Instruction::Op(opcode));
+                continue;
+            }
+        };
+        // PUSHDATA of nothing is written without a data token
+        let data = match tokens.peek() {
+            Some(next) if max_len > 0 && Opcode::from_name(next).is_none() => {
+                let next = tokens.next().expect("peeked");
+                hex::decode(next).map_err(|_| ScriptError::InvalidPush(format!("{} {}", token, next)))?
+            }
+            _ => Vec::new(),
+        };
+        let fits = match opcode {
+            Opcode::PushBytes(len) => data.len() == len as usize,
+            _ => data.len() <= max_len,
+        };
+        if !fits {
+            return Err(ScriptError::InvalidPush(format!("{} {}", token, hex::encode(&data))));
+        }
+        instructions.push(Instruction::Push(opcode, data));
+    }
+    Ok(encode_script(&instructions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opcode_byte_round_trip() {
+        for byte in 0..=255u8 {
+            assert_eq!(Opcode::from_u8(byte).to_u8(), byte);
+        }
+        assert_eq!(Opcode::from_u8(0x76), Opcode::Dup);
+        assert_eq!(Opcode::from_u8(0x51), Opcode::PushNum(1));
+        assert_eq!(Opcode::from_u8(0xba), Opcode::CheckSigAdd);
+        assert_eq!(Opcode::from_u8(0xbb), Opcode::Unassigned(0xbb));
+    }
+
+    #[test]
+    fn test_parse_p2pkh() {
+        let instructions = parse_script_hex("76a914dd6892bcbea5d56471d097923d1a81b773678d1288ac").unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                Instruction::Op(Opcode::Dup),
+                Instruction::Op(Opcode::Hash160),
+                Instruction::Push(
+                    Opcode::PushBytes(20),
+                    hex::decode("dd6892bcbea5d56471d097923d1a81b773678d12").unwrap()
+                ),
+                Instruction::Op(Opcode::EqualVerify),
+                Instruction::Op(Opcode::CheckSig),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_push_variants() {
+        let mut script = vec![0x00, 0x4c, 0x02, 0xaa, 0xbb, 0x4d, 0x01, 0x00, 0xcc, 0x4e, 0x00, 0x00, 0x00, 0x00, 0x60];
+        script.extend([0x4d, 0x00, 0x01]);
+        script.extend([0x11; 256]);
+        let instructions = parse_script(&script).unwrap();
+        assert_eq!(instructions[0], Instruction::Push(Opcode::PushBytes(0), vec![]));
+        assert_eq!(instructions[1], Instruction::Push(Opcode::PushData1, vec![0xaa, 0xbb]));
+        assert_eq!(instructions[2], Instruction::Push(Opcode::PushData2, vec![0xcc]));
+        assert_eq!(instructions[3], Instruction::Push(Opcode::PushData4, vec![]));
+        assert_eq!(instructions[4], Instruction::Op(Opcode::PushNum(16)));
+        assert_eq!(instructions[5].push_data().unwrap().len(), 256);
+        assert!(instructions.iter().all(Instruction::is_push));
+
+        // Encoding gives back the exact bytes, including non-minimal pushes
+        assert_eq!(encode_script(&instructions), script);
+        let total: usize = instructions.iter().map(Instruction::encoded_len).sum();
+        assert_eq!(total, script.len());
+
+        // Minimal pushes pick the shortest form
+        assert_eq!(Instruction::push(&[]), Instruction::Push(Opcode::PushBytes(0), vec![]));
+        assert_eq!(Instruction::push(&[0xcc; 76]).encoded_len(), 78);
+        assert_eq!(Instruction::push(&[0x11; 256]), instructions[5]);
+    }
+
+    #[test]
+    fn test_opcode_names() {
+        for byte in 0..=255u8 {
+            let opcode = Opcode::from_u8(byte);
+            assert_eq!(Opcode::from_name(&opcode.to_string()), Some(opcode), "{}", opcode);
+        }
+        assert_eq!(Opcode::PushNumNeg1.to_string(), "OP_PUSHNUM_NEG1");
+        assert_eq!(Opcode::CheckLockTimeVerify.to_string(), "OP_CLTV");
+        assert_eq!(Opcode::Unassigned(0xbb).to_string(), "OP_RETURN_187");
+        assert_eq!(Opcode::Invalid.to_string(), "OP_INVALIDOPCODE");
+        assert_eq!(Opcode::from_name("OP_16"), Some(Opcode::PushNum(16)));
+        assert_eq!(Opcode::from_name("OP_CHECKSEQUENCEVERIFY"), Some(Opcode::CheckSequenceVerify));
+        assert_eq!(Opcode::from_name("OP_PUSHBYTES_76"), None);
+        assert_eq!(Opcode::from_name("OP_17"), None);
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let script = hex::decode("76a914dd6892bcbea5d56471d097923d1a81b773678d1288ac").unwrap();
+        assert_eq!(
+            disassemble(&script),
+            "OP_DUP OP_HASH160 OP_PUSHBYTES_20 dd6892bcbea5d56471d097923d1a81b773678d12 OP_EQUALVERIFY OP_CHECKSIG"
+        );
+        assert_eq!(disassemble(&[0x00, 0x4f, 0x52, 0x4c, 0x01, 0xaa, 0x4d, 0x00, 0x00]), "OP_0 OP_PUSHNUM_NEG1 OP_PUSHNUM_2 OP_PUSHDATA1 aa OP_PUSHDATA2");
+        assert_eq!(disassemble(&[]), "");
+        // Truncated scripts end the way esplora's do
+        assert_eq!(disassemble(&[0x76, 0x14, 0x00]), "OP_DUP OP_PUSHBYTES_20 <push past end>");
+        assert_eq!(disassemble(&[0x76, 0x4d, 0x01]), "OP_DUP<unexpected end>");
+    }
+
+    #[test]
+    fn test_assemble() {
+        let asm = "OP_PUSHNUM_2 OP_PUSHBYTES_3 010203 OP_PUSHDATA1 aabb OP_PUSHDATA1 OP_0 OP_CHECKMULTISIG OP_RETURN_200";
+        let script = assemble(asm).unwrap();
+        assert_eq!(hex::encode(&script), "52030102034c02aabb4c0000aec8");
+        assert_eq!(disassemble(&script), asm);
+
+        // Bare hex becomes the shortest push, and readable aliases are accepted
+        assert_eq!(assemble("OP_1 dead OP_TRUE OP_CHECKLOCKTIMEVERIFY").unwrap(), vec![0x51, 0x02, 0xde, 0xad, 0x51, 0xb1]);
+
+        assert_eq!(assemble("OP_FOO"), Err(ScriptError::UnknownToken("OP_FOO".to_string())));
+        assert_eq!(assemble("OP_PUSHBYTES_2 aa"), Err(ScriptError::InvalidPush("OP_PUSHBYTES_2 aa".to_string())));
+        assert!(matches!(assemble("OP_PUSHBYTES_1 zz"), Err(ScriptError::InvalidPush(_))));
+    }
+
+    #[test]
+    fn test_parse_truncated_push() {
+        assert_eq!(parse_script(&[0x76, 0x14, 0x00]), Err(ScriptError::TruncatedPush { offset: 1 }));
+        assert_eq!(parse_script(&[0x4c]), Err(ScriptError::TruncatedPush { offset: 0 }));
+        assert_eq!(parse_script(&[0x4e, 0xff, 0xff, 0xff, 0xff]), Err(ScriptError::TruncatedPush { offset: 0 }));
+        assert!(matches!(parse_script_hex("zz"), Err(ScriptError::InvalidHex(_))));
+    }
+}
@@ -0,0 +1,221 @@
+use crate::interpreter::MAX_PUBKEYS_PER_MULTISIG;
+use crate::script::{parse_script, Instruction, Opcode};
+
+/// The standard output templates, recognised from scriptPubKey bytes alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptType {
+    /// `<pubkey> OP_CHECKSIG`
+    P2pk,
+    /// `OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG`
+    P2pkh,
+    /// `OP_HASH160 <20 bytes> OP_EQUAL`
+    P2sh,
+    /// `OP_0 <20 bytes>`
+    P2wpkh,
+    /// `OP_0 <32 bytes>`
+    P2wsh,
+    /// `OP_1 <32 bytes>`
+    P2tr,
+    /// `OP_m <pubkey>... OP_n OP_CHECKMULTISIG`, with n up to 20 (17-20 written as pushes).
+    Multisig { required: u8, keys: u8 },
+    /// `OP_RETURN` followed only by pushes.
+    NullData,
+    /// A witness program of a version (or v1 length) with no defined meaning yet.
+    WitnessUnknown { version: u8 },
+    NonStandard,
+}
+
+// Public keys are 33 bytes (compressed) or 65 bytes (uncompressed/hybrid) with a matching prefix
+fn is_public_key(data: &[u8]) -> bool {
+    match data.first() {
+        Some(0x02 | 0x03) => data.len() == 33,
+        Some(0x04 | 0x06 | 0x07) => data.len() == 65,
+        _ => false,
+    }
+}
+
+// A multisig key or signature count: OP_1..OP_16, or a one-byte push of 17-20
+fn multisig_count(instruction: &Instruction) -> Option<u8> {
+    match instruction {
+        Instruction::Op(Opcode::PushNum(number)) => Some(*number),
+        Instruction::Push(Opcode::PushBytes(1), data) => match data[0] as usize {
+            number @ 17..=MAX_PUBKEYS_PER_MULTISIG => Some(number as u8),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The version and program of a witness output: a version opcode followed by one 2-40 byte push.
+pub fn witness_program(script: &[u8]) -> Option<(u8, &[u8])> {
+    let version = match Opcode::from_u8(*script.first()?) {
+        Opcode::PushBytes(0) => 0,
+        Opcode::PushNum(version) => version,
+        _ => return None,
+    };
+    let len = *script.get(1)? as usize;
+    ((2..=40).contains(&len) && script.len() == len + 2).then(|| (version, &script[2..]))
+}
+
+impl ScriptType {
+    /// Classifies a scriptPubKey the way Bitcoin Core's `Solver` does.
+    pub fn classify(script: &[u8]) -> ScriptType {
+        if let Some((version, program)) = witness_program(script) {
+            return match (version, program.len()) {
+                (0, 20) => ScriptType::P2wpkh,
+                (0, 32) => ScriptType::P2wsh,
+                (0, _) => ScriptType::NonStandard,
+                (1, 32) => ScriptType::P2tr,
+                _ => ScriptType::WitnessUnknown { version },
+            };
+        }
+        let Ok(instructions) = parse_script(script) else {
+            return ScriptType::NonStandard;
+        };
+        match instructions.as_slice() {
+            [Instruction::Op(Opcode::Hash160), Instruction::Push(Opcode::PushBytes(20), _), Instruction::Op(Opcode::Equal)] => {
+                ScriptType::P2sh
+            }
+            [Instruction::Op(Opcode::Return), rest @ ..] if rest.iter().all(Instruction::is_push) => ScriptType::NullData,
+            [Instruction::Push(_, key), Instruction::Op(Opcode::CheckSig)] if is_public_key(key) => ScriptType::P2pk,
+            [Instruction::Op(Opcode::Dup), Instruction::Op(Opcode::Hash160), Instruction::Push(Opcode::PushBytes(20), _), Instruction::Op(Opcode::EqualVerify), Instruction::Op(Opcode::CheckSig)] => {
+                ScriptType::P2pkh
+            }
+            [required, keys @ .., count, Instruction::Op(Opcode::CheckMultiSig)] => {
+                match (multisig_count(required), multisig_count(count)) {
+                    (Some(required), Some(count))
+                        if required <= count
+                            && keys.len() == count as usize
+                            && keys.iter().all(|key| key.push_data().is_some_and(is_public_key)) =>
+                    {
+                        ScriptType::Multisig { required, keys: count }
+                    }
+                    _ => ScriptType::NonStandard,
+                }
+            }
+            _ => ScriptType::NonStandard,
+        }
+    }
+}
+
+/// The `scriptpubkey_type` label esplora gives `script`.
+///
+/// Esplora labels any script starting with OP_RETURN as `op_return` and calls bare
+/// multisig, future witness versions and nonstandard scripts all `unknown`.
+pub fn esplora_type(script: &[u8]) -> &'static str {
+    match ScriptType::classify(script) {
+        ScriptType::P2pk => "p2pk",
+        ScriptType::P2pkh => "p2pkh",
+        ScriptType::P2sh => "p2sh",
+        ScriptType::P2wpkh => "v0_p2wpkh",
+        ScriptType::P2wsh => "v0_p2wsh",
+        ScriptType::P2tr => "v1_p2tr",
+        ScriptType::NullData => "op_return",
+        ScriptType::Multisig { .. } | ScriptType::WitnessUnknown { .. } | ScriptType::NonStandard => match script.first() {
+            None => "empty",
+            Some(0x6a) => "op_return",
+            Some(&byte) if is_provably_unspendable(Opcode::from_u8(byte)) => "provably_unspendable",
+            Some(_) => "unknown",
+        },
+    }
+}
+
+// Opcodes that fail the script whenever they are reached first, executed or not
+fn is_provably_unspendable(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        Opcode::Reserved
+            | Opcode::Ver
+            | Opcode::VerIf
+            | Opcode::VerNotIf
+            | Opcode::Reserved1
+            | Opcode::Reserved2
+            | Opcode::Cat
+            | Opcode::Substr
+            | Opcode::Left
+            | Opcode::Right
+            | Opcode::Invert
+            | Opcode::And
+            | Opcode::Or
+            | Opcode::Xor
+            | Opcode::Mul2
+            | Opcode::Div2
+            | Opcode::Mul
+            | Opcode::Div
+            | Opcode::Mod
+            | Opcode::LShift
+            | Opcode::RShift
+            | Opcode::CheckSigAdd
+            | Opcode::Unassigned(_)
+            | Opcode::Invalid
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::assemble;
+
+    fn classify_asm(asm: &str) -> ScriptType {
+        ScriptType::classify(&assemble(asm).unwrap())
+    }
+
+    #[test]
+    fn test_classify_templates() {
+        let key = format!("02{}", "11".repeat(32));
+        let uncompressed = format!("04{}", "22".repeat(64));
+        let hash = "33".repeat(20);
+        let program = "44".repeat(32);
+
+        assert_eq!(classify_asm(&format!("{} OP_CHECKSIG", key)), ScriptType::P2pk);
+        assert_eq!(classify_asm(&format!("{} OP_CHECKSIG", uncompressed)), ScriptType::P2pk);
+        assert_eq!(classify_asm(&format!("OP_DUP OP_HASH160 {} OP_EQUALVERIFY OP_CHECKSIG", hash)), ScriptType::P2pkh);
+        assert_eq!(classify_asm(&format!("OP_HASH160 {} OP_EQUAL", hash)), ScriptType::P2sh);
+        assert_eq!(classify_asm(&format!("OP_0 {}", hash)), ScriptType::P2wpkh);
+        assert_eq!(classify_asm(&format!("OP_0 {}", program)), ScriptType::P2wsh);
+        assert_eq!(classify_asm(&format!("OP_1 {}", program)), ScriptType::P2tr);
+        assert_eq!(classify_asm(&format!("OP_1 {}", hash)), ScriptType::WitnessUnknown { version: 1 });
+        assert_eq!(classify_asm("OP_16 beef"), ScriptType::WitnessUnknown { version: 16 });
+        assert_eq!(
+            classify_asm(&format!("OP_1 {} {} OP_2 OP_CHECKMULTISIG", key, uncompressed)),
+            ScriptType::Multisig { required: 1, keys: 2 }
+        );
+        let twenty_keys = vec![key.as_str(); 20].join(" ");
+        assert_eq!(
+            classify_asm(&format!("OP_PUSHBYTES_1 12 {} OP_PUSHBYTES_1 14 OP_CHECKMULTISIG", twenty_keys)),
+            ScriptType::Multisig { required: 18, keys: 20 }
+        );
+        assert_eq!(classify_asm("OP_RETURN OP_PUSHBYTES_4 deadbeef OP_5"), ScriptType::NullData);
+        assert_eq!(classify_asm("OP_RETURN"), ScriptType::NullData);
+    }
+
+    #[test]
+    fn test_classify_near_misses() {
+        let key = format!("02{}", "11".repeat(32));
+        // v0 programs must be 20 or 32 bytes
+        assert_eq!(classify_asm(&format!("OP_0 {}", "55".repeat(21))), ScriptType::NonStandard);
+        // The key must look like a public key
+        assert_eq!(classify_asm(&format!("05{} OP_CHECKSIG", "11".repeat(32))), ScriptType::NonStandard);
+        // More signatures required than keys, or a key count that does not match
+        assert_eq!(classify_asm(&format!("OP_2 {} OP_1 OP_CHECKMULTISIG", key)), ScriptType::NonStandard);
+        assert_eq!(classify_asm(&format!("OP_1 {} OP_2 OP_CHECKMULTISIG", key)), ScriptType::NonStandard);
+        // 21 keys is more than OP_CHECKMULTISIG takes
+        let keys = vec![key.as_str(); 21].join(" ");
+        assert_eq!(classify_asm(&format!("OP_1 {} OP_PUSHBYTES_1 15 OP_CHECKMULTISIG", keys)), ScriptType::NonStandard);
+        assert_eq!(classify_asm("OP_0 OP_0 OP_CHECKMULTISIG"), ScriptType::NonStandard);
+        // Data after OP_RETURN must be pushes only
+        assert_eq!(classify_asm("OP_RETURN OP_DUP"), ScriptType::NonStandard);
+        assert_eq!(ScriptType::classify(&[0x76, 0x14]), ScriptType::NonStandard);
+    }
+
+    #[test]
+    fn test_esplora_type() {
+        let key = format!("02{}", "11".repeat(32));
+        let multisig = assemble(&format!("OP_1 {} OP_1 OP_CHECKMULTISIG", key)).unwrap();
+        assert_eq!(esplora_type(&multisig), "unknown");
+        assert_eq!(esplora_type(&[0x6a, 0x76]), "op_return");
+        assert_eq!(esplora_type(&[0xba]), "provably_unspendable");
+        assert_eq!(esplora_type(&[]), "empty");
+        assert_eq!(esplora_type(&hex::decode("0014ddd7ef12ea6f78edcc1e8e7afa12c01ab59f3339").unwrap()), "v0_p2wpkh");
+    }
+}
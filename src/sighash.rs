@@ -0,0 +1,466 @@
+use secp256k1::{ecdsa::Signature, schnorr, Message, PublicKey, Secp256k1, XOnlyPublicKey};
+use sha2::{Digest, Sha256};
+
+use crate::encoding::{serialize_without_witness, txid_to_bytes, Encodable, EncodingError};
+use crate::interpreter::{ExecutionData, SignatureChecker, SigVersion};
+use crate::script::{encode_script, parse_script, Instruction, Opcode};
+use crate::transaction_loader::Transaction;
+use crate::transaction_validator::{double_sha256, tagged_hash};
+
+/// Lock times below this are block heights, the rest are timestamps.
+const LOCKTIME_THRESHOLD: i64 = 500_000_000;
+/// Sequence flags from BIP68.
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: i64 = 1 << 31;
+const SEQUENCE_LOCKTIME_TYPE_FLAG: i64 = 1 << 22;
+const SEQUENCE_LOCKTIME_MASK: i64 = 0x0000_ffff;
+/// Signature hash types; the low five bits pick the outputs, ANYONECANPAY is a flag on top.
+const SIGHASH_NONE: u32 = 0x02;
+const SIGHASH_SINGLE: u32 = 0x03;
+const SIGHASH_ANYONECANPAY: u32 = 0x80;
+/// The taproot-only hash type of a 64-byte signature, which signs like ALL.
+const SIGHASH_DEFAULT: u32 = 0x00;
+
+/// The legacy signature hash of input `input_index`: the transaction with every
+/// scriptSig emptied except the signed one, which is replaced by `script_code` without
+/// its OP_CODESEPARATORs, followed by the 4-byte hash type.
+///
+/// NONE drops the outputs and SINGLE keeps only the one at the input's index (blanking
+/// those before it); both let the other inputs' sequences change. ANYONECANPAY keeps
+/// only the signed input. SINGLE without a matching output signs the hash 1, as Bitcoin
+/// Core always has.
+pub fn legacy_sighash(
+    transaction: &Transaction,
+    input_index: usize,
+    script_code: &[u8],
+    hash_type: u32,
+) -> Result<[u8; 32], EncodingError> {
+    let base_type = hash_type & 0x1f;
+    if base_type == SIGHASH_SINGLE && input_index >= transaction.vout.len() {
+        let mut one = [0; 32];
+        one[0] = 1;
+        return Ok(one);
+    }
+
+    let mut transaction = transaction.clone();
+    let script_code = hex::encode(remove_code_separators(script_code));
+    for (index, vin) in transaction.vin.iter_mut().enumerate() {
+        vin.scriptsig = if index == input_index { script_code.clone() } else { String::new() };
+        if index != input_index && (base_type == SIGHASH_NONE || base_type == SIGHASH_SINGLE) {
+            vin.sequence = 0;
+        }
+    }
+    if base_type == SIGHASH_NONE {
+        transaction.vout.clear();
+    } else if base_type == SIGHASH_SINGLE {
+        transaction.vout.truncate(input_index + 1);
+        for vout in &mut transaction.vout[..input_index] {
+            vout.value = -1;
+            vout.scriptpubkey = String::new();
+        }
+    }
+    if hash_type & SIGHASH_ANYONECANPAY != 0 {
+        transaction.vin = vec![transaction.vin.swap_remove(input_index)];
+    }
+
+    let mut preimage = serialize_without_witness(&transaction)?;
+    preimage.extend_from_slice(&hash_type.to_le_bytes());
+    Ok(double_sha256(&preimage))
+}
+
+// Drops every OP_CODESEPARATOR from a legacy scriptCode before it is signed
+fn remove_code_separators(script_code: &[u8]) -> Vec<u8> {
+    match parse_script(script_code) {
+        Ok(instructions) => {
+            let kept: Vec<Instruction> = instructions
+                .into_iter()
+                .filter(|instruction| *instruction != Instruction::Op(Opcode::CodeSeparator))
+                .collect();
+            encode_script(&kept)
+        }
+        Err(_) => script_code.to_vec(),
+    }
+}
+
+/// The BIP143 signature hash of input `input_index`, which spends `amount` satoshis,
+/// for segwit v0 scripts. `script_code` is the script being executed, without a length prefix.
+///
+/// ANYONECANPAY leaves the other inputs out, NONE signs no outputs and SINGLE only the
+/// output with the same index as the input (none at all when there is no such output).
+pub fn bip143_sighash(
+    transaction: &Transaction,
+    input_index: usize,
+    script_code: &[u8],
+    amount: u64,
+    hash_type: u32,
+) -> Result<[u8; 32], EncodingError> {
+    let anyone_can_pay = hash_type & SIGHASH_ANYONECANPAY != 0;
+    let base_type = hash_type & 0x1f;
+
+    let mut hash_prevouts = [0; 32];
+    let mut hash_sequence = [0; 32];
+    if !anyone_can_pay {
+        let mut prevouts = Vec::new();
+        let mut sequences = Vec::new();
+        for vin in &transaction.vin {
+            prevouts.extend_from_slice(&txid_to_bytes(&vin.txid)?);
+            vin.vout.consensus_encode(&mut prevouts)?;
+            vin.sequence.consensus_encode(&mut sequences)?;
+        }
+        hash_prevouts = double_sha256(&prevouts);
+        if base_type != SIGHASH_SINGLE && base_type != SIGHASH_NONE {
+            hash_sequence = double_sha256(&sequences);
+        }
+    }
+
+    let mut hash_outputs = [0; 32];
+    if base_type != SIGHASH_SINGLE && base_type != SIGHASH_NONE {
+        let mut outputs = Vec::new();
+        for vout in &transaction.vout {
+            vout.consensus_encode(&mut outputs)?;
+        }
+        hash_outputs = double_sha256(&outputs);
+    } else if base_type == SIGHASH_SINGLE {
+        if let Some(vout) = transaction.vout.get(input_index) {
+            let mut output = Vec::new();
+            vout.consensus_encode(&mut output)?;
+            hash_outputs = double_sha256(&output);
+        }
+    }
+    let vin = &transaction.vin[input_index];
+
+    let mut preimage = Vec::new();
+    transaction.version.consensus_encode(&mut preimage)?;
+    preimage.extend_from_slice(&hash_prevouts);
+    preimage.extend_from_slice(&hash_sequence);
+    preimage.extend_from_slice(&txid_to_bytes(&vin.txid)?);
+    vin.vout.consensus_encode(&mut preimage)?;
+    script_code.to_vec().consensus_encode(&mut preimage)?;
+    amount.consensus_encode(&mut preimage)?;
+    vin.sequence.consensus_encode(&mut preimage)?;
+    preimage.extend_from_slice(&hash_outputs);
+    transaction.locktime.consensus_encode(&mut preimage)?;
+    hash_type.consensus_encode(&mut preimage)?;
+    Ok(double_sha256(&preimage))
+}
+
+/// The BIP341 signature hash of taproot input `input_index` with hash type `hash_type`,
+/// or `None` when the hash type is undefined or SINGLE has no matching output.
+///
+/// Unlike the earlier schemes it commits to the amounts and scriptPubKeys of every
+/// spent output, not only the signed one, and to the annex if `execution` has one.
+/// Script path signatures (BIP342) also commit to the tapleaf hash and last
+/// OP_CODESEPARATOR position in `execution`.
+pub fn taproot_sighash(
+    transaction: &Transaction,
+    input_index: usize,
+    hash_type: u32,
+    execution: &ExecutionData,
+) -> Option<[u8; 32]> {
+    if !matches!(hash_type, 0x00..=0x03 | 0x81..=0x83) {
+        return None;
+    }
+    let anyone_can_pay = hash_type & SIGHASH_ANYONECANPAY != 0;
+    let base_type = hash_type & 0x03;
+
+    // Epoch 0
+    let mut message = vec![0x00, hash_type as u8];
+    transaction.version.consensus_encode(&mut message).ok()?;
+    transaction.locktime.consensus_encode(&mut message).ok()?;
+    if !anyone_can_pay {
+        let (mut prevouts, mut amounts, mut script_pubkeys, mut sequences) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        for vin in &transaction.vin {
+            prevouts.extend_from_slice(&txid_to_bytes(&vin.txid).ok()?);
+            vin.vout.consensus_encode(&mut prevouts).ok()?;
+            vin.prevout.value.consensus_encode(&mut amounts).ok()?;
+            hex::decode(&vin.prevout.scriptpubkey).ok()?.consensus_encode(&mut script_pubkeys).ok()?;
+            vin.sequence.consensus_encode(&mut sequences).ok()?;
+        }
+        for data in [prevouts, amounts, script_pubkeys, sequences] {
+            message.extend_from_slice(&Sha256::digest(data));
+        }
+    }
+    if base_type != SIGHASH_NONE && base_type != SIGHASH_SINGLE {
+        let mut outputs = Vec::new();
+        for vout in &transaction.vout {
+            vout.consensus_encode(&mut outputs).ok()?;
+        }
+        message.extend_from_slice(&Sha256::digest(outputs));
+    }
+
+    // Spend type: the extension flag (1 for tapscript) and whether there is an annex
+    let extension_flag = if execution.tapleaf_hash.is_some() { 1 } else { 0 };
+    message.push(extension_flag * 2 + execution.annex.is_some() as u8);
+    if anyone_can_pay {
+        let vin = transaction.vin.get(input_index)?;
+        message.extend_from_slice(&txid_to_bytes(&vin.txid).ok()?);
+        vin.vout.consensus_encode(&mut message).ok()?;
+        vin.prevout.value.consensus_encode(&mut message).ok()?;
+        hex::decode(&vin.prevout.scriptpubkey).ok()?.consensus_encode(&mut message).ok()?;
+        vin.sequence.consensus_encode(&mut message).ok()?;
+    } else {
+        (input_index as u32).consensus_encode(&mut message).ok()?;
+    }
+    if let Some(annex) = &execution.annex {
+        let mut annex_bytes = Vec::new();
+        annex.consensus_encode(&mut annex_bytes).ok()?;
+        message.extend_from_slice(&Sha256::digest(annex_bytes));
+    }
+    if base_type == SIGHASH_SINGLE {
+        let mut output = Vec::new();
+        transaction.vout.get(input_index)?.consensus_encode(&mut output).ok()?;
+        message.extend_from_slice(&Sha256::digest(output));
+    }
+    if let Some(tapleaf_hash) = execution.tapleaf_hash {
+        // Key version 0
+        message.extend_from_slice(&tapleaf_hash);
+        message.push(0x00);
+        execution.code_separator_position.consensus_encode(&mut message).ok()?;
+    }
+    Some(tagged_hash("TapSighash", &message))
+}
+
+/// Checks signatures and lock times against one input of a transaction.
+pub struct TransactionSignatureChecker<'a> {
+    pub transaction: &'a Transaction,
+    pub input_index: usize,
+}
+
+impl<'a> TransactionSignatureChecker<'a> {
+    pub fn new(transaction: &'a Transaction, input_index: usize) -> TransactionSignatureChecker<'a> {
+        TransactionSignatureChecker { transaction, input_index }
+    }
+
+    fn sighash(&self, script_code: &[u8], hash_type: u32, sig_version: SigVersion) -> Option<[u8; 32]> {
+        match sig_version {
+            SigVersion::Base => legacy_sighash(self.transaction, self.input_index, script_code, hash_type).ok(),
+            SigVersion::WitnessV0 => {
+                let amount = self.transaction.vin.get(self.input_index)?.prevout.value;
+                bip143_sighash(self.transaction, self.input_index, script_code, amount as u64, hash_type).ok()
+            }
+            // Taproot only takes Schnorr signatures
+            SigVersion::Taproot | SigVersion::Tapscript => None,
+        }
+    }
+
+    fn sequence(&self) -> Option<i64> {
+        self.transaction.vin.get(self.input_index).map(|vin| vin.sequence as i64)
+    }
+}
+
+impl SignatureChecker for TransactionSignatureChecker<'_> {
+    fn check_ecdsa_signature(&self, signature: &[u8], public_key: &[u8], script_code: &[u8], sig_version: SigVersion) -> bool {
+        let Some((&hash_type, der)) = signature.split_last() else {
+            return false;
+        };
+        let (Ok(mut signature), Ok(public_key)) = (Signature::from_der(der), PublicKey::from_slice(public_key)) else {
+            return false;
+        };
+        // High-S signatures are non-standard but valid by consensus
+        signature.normalize_s();
+        let Some(digest) = self.sighash(script_code, hash_type as u32, sig_version) else {
+            return false;
+        };
+        Secp256k1::verification_only()
+            .verify_ecdsa(&Message::from_digest(digest), &signature, &public_key)
+            .is_ok()
+    }
+
+    fn check_schnorr_signature(
+        &self,
+        signature: &[u8],
+        public_key: &[u8],
+        sig_version: SigVersion,
+        execution: &ExecutionData,
+    ) -> bool {
+        let (signature, hash_type) = match signature.len() {
+            64 => (signature, SIGHASH_DEFAULT),
+            // An explicit hash type of 0x00 would give two encodings of the same signature
+            65 if signature[64] != SIGHASH_DEFAULT as u8 => (&signature[..64], signature[64] as u32),
+            _ => return false,
+        };
+        let (Ok(signature), Ok(public_key)) = (schnorr::Signature::from_slice(signature), XOnlyPublicKey::from_slice(public_key)) else {
+            return false;
+        };
+        let digest = match sig_version {
+            SigVersion::Taproot | SigVersion::Tapscript => {
+                taproot_sighash(self.transaction, self.input_index, hash_type, execution)
+            }
+            SigVersion::Base | SigVersion::WitnessV0 => None,
+        };
+        let Some(digest) = digest else {
+            return false;
+        };
+        Secp256k1::verification_only()
+            .verify_schnorr(&signature, &Message::from_digest(digest), &public_key)
+            .is_ok()
+    }
+
+    fn check_lock_time(&self, lock_time: i64) -> bool {
+        let tx_lock_time = self.transaction.locktime as i64;
+        // Heights and timestamps cannot be compared with each other
+        let same_kind = (tx_lock_time < LOCKTIME_THRESHOLD) == (lock_time < LOCKTIME_THRESHOLD);
+        // A final input would let the lock time be bypassed
+        same_kind && lock_time <= tx_lock_time && self.sequence() != Some(0xffff_ffff)
+    }
+
+    fn check_sequence(&self, sequence: i64) -> bool {
+        let Some(tx_sequence) = self.sequence() else {
+            return false;
+        };
+        if self.transaction.version < 2 || tx_sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return false;
+        }
+        let mask = SEQUENCE_LOCKTIME_TYPE_FLAG | SEQUENCE_LOCKTIME_MASK;
+        let (sequence, tx_sequence) = (sequence & mask, tx_sequence & mask);
+        let same_kind = (tx_sequence < SEQUENCE_LOCKTIME_TYPE_FLAG) == (sequence < SEQUENCE_LOCKTIME_TYPE_FLAG);
+        same_kind && sequence <= tx_sequence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction_loader::load_json_transaction;
+
+    #[test]
+    fn test_legacy_sighash_verifies_p2pkh() {
+        // One p2pkh input: <sig> <pubkey> spending OP_DUP OP_HASH160 <hash> OP_EQUALVERIFY OP_CHECKSIG
+        let transaction = load_json_transaction("00d12b523d8b7ad90e2269767478764c243625539dc59bcd457d14ca1aa4e38c");
+        let script_sig = hex::decode(&transaction.vin[0].scriptsig).unwrap();
+        let script_code = hex::decode(&transaction.vin[0].prevout.scriptpubkey).unwrap();
+        let instructions = crate::script::parse_script(&script_sig).unwrap();
+        let (signature, public_key) = (instructions[0].push_data().unwrap(), instructions[1].push_data().unwrap());
+
+        let checker = TransactionSignatureChecker::new(&transaction, 0);
+        assert!(checker.check_ecdsa_signature(signature, public_key, &script_code, SigVersion::Base));
+        // Any other scriptCode or input signs a different message
+        assert!(!checker.check_ecdsa_signature(signature, public_key, &script_code[1..], SigVersion::Base));
+        assert!(!checker.check_ecdsa_signature(&signature[..signature.len() - 1], public_key, &script_code, SigVersion::Base));
+    }
+
+    #[test]
+    fn test_lock_time_checks() {
+        let mut transaction = load_json_transaction("00d12b523d8b7ad90e2269767478764c243625539dc59bcd457d14ca1aa4e38c");
+        transaction.version = 2;
+        transaction.locktime = 800_000;
+        transaction.vin[0].sequence = 0xffff_fffe;
+        let checker = TransactionSignatureChecker::new(&transaction, 0);
+        assert!(checker.check_lock_time(800_000));
+        assert!(!checker.check_lock_time(800_001));
+        // A timestamp cannot be satisfied by a height
+        assert!(!checker.check_lock_time(LOCKTIME_THRESHOLD));
+
+        transaction.vin[0].sequence = 10;
+        let checker = TransactionSignatureChecker::new(&transaction, 0);
+        assert!(checker.check_sequence(10));
+        assert!(!checker.check_sequence(11));
+        assert!(!checker.check_sequence(SEQUENCE_LOCKTIME_TYPE_FLAG | 1));
+    }
+
+    #[test]
+    fn test_bip143_sighash_types() {
+        // Input 1 signs with SIGHASH_SINGLE|ANYONECANPAY
+        let transaction = load_json_transaction("c5716cfa78d5a337c0b81bc8ce87481a5ba1ff304f907367b68a5c3d2fee7a4b");
+        let script_code = hex::decode(format!("76a914{}88ac", &transaction.vin[1].prevout.scriptpubkey[4..])).unwrap();
+        let amount = transaction.vin[1].prevout.value as u64;
+        let digest = |transaction: &Transaction, hash_type| bip143_sighash(transaction, 1, &script_code, amount, hash_type).unwrap();
+        let signed = digest(&transaction, 0x83);
+
+        // Other inputs and outputs can change without invalidating the signature
+        let mut changed = transaction.clone();
+        changed.vin[0].sequence ^= 1;
+        changed.vout[0].value += 1;
+        assert_eq!(digest(&changed, 0x83), signed);
+        assert_eq!(digest(&changed, 0x03), digest(&transaction, 0x03));
+        assert_ne!(digest(&changed, 0x01), digest(&transaction, 0x01));
+        // Without ANYONECANPAY the other outpoints are still signed
+        changed.vin[0].vout ^= 1;
+        assert_eq!(digest(&changed, 0x83), signed);
+        assert_ne!(digest(&changed, 0x03), digest(&transaction, 0x03));
+        // but not the output at the input's index
+        changed.vout[1].value += 1;
+        assert_ne!(digest(&changed, 0x83), signed);
+
+        // NONE signs no outputs at all
+        let mut changed = transaction.clone();
+        changed.vout.iter_mut().for_each(|vout| vout.value += 1);
+        assert_eq!(digest(&changed, 0x02), digest(&transaction, 0x02));
+        assert_ne!(digest(&changed, 0x01), digest(&transaction, 0x01));
+    }
+
+    #[test]
+    fn test_legacy_sighash_types() {
+        // Two inputs and two outputs
+        let transaction = load_json_transaction("204ac1129b8c7dd69d0459f57521bf88acbdeb6d57caa84a0b8a391826faf155");
+        let script_code = hex::decode(&transaction.vin[0].prevout.scriptpubkey).unwrap();
+        let digest = |transaction: &Transaction, hash_type| legacy_sighash(transaction, 0, &script_code, hash_type).unwrap();
+
+        // NONE and SINGLE let other inputs change their sequence, ANYONECANPAY lets them go entirely
+        let mut changed = transaction.clone();
+        changed.vin[1].sequence ^= 1;
+        assert_ne!(digest(&changed, 0x01), digest(&transaction, 0x01));
+        assert_eq!(digest(&changed, 0x02), digest(&transaction, 0x02));
+        assert_eq!(digest(&changed, 0x03), digest(&transaction, 0x03));
+        changed.vin.pop();
+        assert_ne!(digest(&changed, 0x03), digest(&transaction, 0x03));
+        assert_eq!(digest(&changed, 0x81), digest(&transaction, 0x81));
+
+        // NONE signs no outputs and SINGLE only the one at the input's index
+        let mut changed = transaction.clone();
+        changed.vout[1].value += 1;
+        assert_eq!(digest(&changed, 0x02), digest(&transaction, 0x02));
+        assert_eq!(digest(&changed, 0x03), digest(&transaction, 0x03));
+        changed.vout[0].value += 1;
+        assert_ne!(digest(&changed, 0x03), digest(&transaction, 0x03));
+
+        // SINGLE without a matching output signs 1
+        let mut one = [0; 32];
+        one[0] = 1;
+        let mut changed = transaction.clone();
+        changed.vout.truncate(1);
+        assert_eq!(legacy_sighash(&changed, 1, &script_code, 0x03).unwrap(), one);
+
+        // OP_CODESEPARATORs are not part of the signed scriptCode
+        let with_separator = [&[0xab][..], &script_code].concat();
+        assert_eq!(legacy_sighash(&transaction, 0, &with_separator, 0x01).unwrap(), digest(&transaction, 0x01));
+    }
+
+    #[test]
+    fn test_taproot_sighash_types() {
+        // Two inputs and four outputs
+        let transaction = load_json_transaction("e8c0af3c2cc9ae68aae011e89284eb6bbcdc7dd6aec7802273cad17635bf0614");
+        let sighash = |transaction: &Transaction, hash_type| taproot_sighash(transaction, 1, hash_type, &ExecutionData::default());
+        // Only 0x00-0x03 and 0x81-0x83 are defined
+        assert!(sighash(&transaction, 0x04).is_none());
+        assert!(sighash(&transaction, 0x80).is_none());
+        // The default type signs like ALL but hashes differently
+        assert_ne!(sighash(&transaction, 0x00), sighash(&transaction, 0x01));
+
+        // ANYONECANPAY leaves out the other inputs' outpoints, amounts and scripts
+        let mut changed = transaction.clone();
+        changed.vin[0].prevout.value += 1;
+        assert_eq!(sighash(&changed, 0x83), sighash(&transaction, 0x83));
+        assert_ne!(sighash(&changed, 0x03), sighash(&transaction, 0x03));
+
+        // SINGLE needs an output at the input's index
+        changed.vout.truncate(1);
+        assert!(sighash(&changed, 0x83).is_none());
+        assert!(sighash(&changed, 0x82).is_some());
+    }
+
+    #[test]
+    fn test_taproot_sighash_script_path() {
+        let transaction = load_json_transaction("e8c0af3c2cc9ae68aae011e89284eb6bbcdc7dd6aec7802273cad17635bf0614");
+        let key_path = ExecutionData::default();
+        let mut execution = ExecutionData { tapleaf_hash: Some([7; 32]), ..ExecutionData::default() };
+        let leaf_digest = taproot_sighash(&transaction, 1, 0x00, &execution);
+        assert_ne!(leaf_digest, taproot_sighash(&transaction, 1, 0x00, &key_path));
+
+        // Signatures commit to the leaf and to the last executed OP_CODESEPARATOR
+        execution.code_separator_position = 3;
+        assert_ne!(taproot_sighash(&transaction, 1, 0x00, &execution), leaf_digest);
+        execution.code_separator_position = u32::MAX;
+        execution.tapleaf_hash = Some([8; 32]);
+        assert_ne!(taproot_sighash(&transaction, 1, 0x00, &execution), leaf_digest);
+    }
+}
@@ -0,0 +1,369 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Not, Shl, Shr, Sub};
+
+/// An unsigned 256-bit integer, stored as four little-endian 64-bit limbs.
+/// Only the operations needed for targets and chainwork are implemented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct U256([u64; 4]);
+
+impl U256 {
+    pub const ZERO: U256 = U256([0; 4]);
+    pub const ONE: U256 = U256([1, 0, 0, 0]);
+    pub const MAX: U256 = U256([u64::MAX; 4]);
+
+    pub fn from_u64(value: u64) -> U256 {
+        U256([value, 0, 0, 0])
+    }
+
+    pub fn from_be_bytes(bytes: [u8; 32]) -> U256 {
+        let mut limbs = [0u64; 4];
+        for (i, chunk) in bytes.chunks_exact(8).enumerate() {
+            limbs[3 - i] = u64::from_be_bytes(chunk.try_into().expect("chunk is 8 bytes"));
+        }
+        U256(limbs)
+    }
+
+    pub fn from_le_bytes(mut bytes: [u8; 32]) -> U256 {
+        bytes.reverse();
+        U256::from_be_bytes(bytes)
+    }
+
+    pub fn to_be_bytes(self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for i in 0..4 {
+            bytes[i * 8..(i + 1) * 8].copy_from_slice(&self.0[3 - i].to_be_bytes());
+        }
+        bytes
+    }
+
+    pub fn is_zero(&self) -> bool {
+        *self == U256::ZERO
+    }
+
+    /// The lowest 64 bits.
+    pub fn low_u64(&self) -> u64 {
+        self.0[0]
+    }
+
+    /// Number of significant bits (0 for zero).
+    pub fn bits(&self) -> u32 {
+        for i in (0..4).rev() {
+            if self.0[i] != 0 {
+                return 64 * i as u32 + (64 - self.0[i].leading_zeros());
+            }
+        }
+        0
+    }
+
+    fn bit(&self, index: u32) -> bool {
+        self.0[(index / 64) as usize] >> (index % 64) & 1 == 1
+    }
+
+    /// Wrapping addition.
+    fn overflowing_add(self, other: U256) -> (U256, bool) {
+        let mut limbs = [0u64; 4];
+        let mut carry = false;
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let (sum, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = c1 || c2;
+        }
+        (U256(limbs), carry)
+    }
+
+    /// Integer division, returning `None` for a zero divisor.
+    pub fn checked_div(self, divisor: U256) -> Option<U256> {
+        if divisor.is_zero() {
+            return None;
+        }
+        // Binary long division
+        let mut quotient = U256::ZERO;
+        let mut remainder = U256::ZERO;
+        for i in (0..self.bits()).rev() {
+            remainder = remainder << 1;
+            if self.bit(i) {
+                remainder.0[0] |= 1;
+            }
+            if remainder >= divisor {
+                remainder = remainder - divisor;
+                quotient.0[(i / 64) as usize] |= 1 << (i % 64);
+            }
+        }
+        Some(quotient)
+    }
+
+    /// Lossy conversion for difficulty calculations.
+    pub fn to_f64(self) -> f64 {
+        self.0
+            .iter()
+            .rev()
+            .fold(0.0, |acc, &limb| acc * 18446744073709551616.0 + limb as f64)
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &U256) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &U256) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for U256 {
+    type Output = U256;
+
+    /// Panics on overflow, like the primitive integer types in debug builds.
+    fn add(self, other: U256) -> U256 {
+        let (sum, overflow) = self.overflowing_add(other);
+        assert!(!overflow, "U256 addition overflowed");
+        sum
+    }
+}
+
+impl Sub for U256 {
+    type Output = U256;
+
+    /// Panics on underflow.
+    fn sub(self, other: U256) -> U256 {
+        assert!(self >= other, "U256 subtraction underflowed");
+        let (difference, _) = self.overflowing_add(!other + U256::ONE);
+        difference
+    }
+}
+
+impl Not for U256 {
+    type Output = U256;
+
+    fn not(self) -> U256 {
+        U256(self.0.map(|limb| !limb))
+    }
+}
+
+impl Shl<u32> for U256 {
+    type Output = U256;
+
+    fn shl(self, shift: u32) -> U256 {
+        let mut limbs = [0u64; 4];
+        let (limb_shift, bit_shift) = ((shift / 64) as usize, shift % 64);
+        for (i, limb) in limbs.iter_mut().enumerate().skip(limb_shift) {
+            *limb = self.0[i - limb_shift] << bit_shift;
+            if bit_shift > 0 && i > limb_shift {
+                *limb |= self.0[i - limb_shift - 1] >> (64 - bit_shift);
+            }
+        }
+        U256(limbs)
+    }
+}
+
+impl Shr<u32> for U256 {
+    type Output = U256;
+
+    fn shr(self, shift: u32) -> U256 {
+        let mut limbs = [0u64; 4];
+        let (limb_shift, bit_shift) = ((shift / 64) as usize, shift % 64);
+        for (i, limb) in limbs.iter_mut().enumerate().take(4usize.saturating_sub(limb_shift)) {
+            *limb = self.0[i + limb_shift] >> bit_shift;
+            if bit_shift > 0 && i + limb_shift + 1 < 4 {
+                *limb |= self.0[i + limb_shift + 1] << (64 - bit_shift);
+            }
+        }
+        U256(limbs)
+    }
+}
+
+impl fmt::LowerHex for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.to_be_bytes()))
+    }
+}
+
+/// Reasons a compact `bits` value does not describe a usable target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactTargetError {
+    /// The sign bit (0x00800000) is set with a non-zero mantissa.
+    Negative,
+    /// The value does not fit in 256 bits.
+    Overflow,
+    /// The target is zero, which no hash can meet.
+    Zero,
+}
+
+impl fmt::Display for CompactTargetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompactTargetError::Negative => write!(f, "compact target is negative"),
+            CompactTargetError::Overflow => write!(f, "compact target overflows 256 bits"),
+            CompactTargetError::Zero => write!(f, "compact target is zero"),
+        }
+    }
+}
+
+impl std::error::Error for CompactTargetError {}
+
+/// A proof-of-work target: a block hash, read as a little-endian 256-bit
+/// number, must be less than or equal to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Target(U256);
+
+impl Target {
+    /// The difficulty-1 target, compact form 0x1d00ffff.
+    pub const MAX_ATTAINABLE: Target = Target(U256([0, 0, 0, 0x0000_0000_ffff_0000]));
+
+    pub fn from_u256(value: U256) -> Target {
+        Target(value)
+    }
+
+    pub fn to_u256(self) -> U256 {
+        self.0
+    }
+
+    /// Expands compact nBits (exponent byte + 23-bit mantissa + sign bit),
+    /// rejecting the negative, overflowing and zero encodings Bitcoin Core rejects.
+    pub fn from_compact(bits: u32) -> Result<Target, CompactTargetError> {
+        let exponent = bits >> 24;
+        let mantissa = bits & 0x007f_ffff;
+        if mantissa != 0 && bits & 0x0080_0000 != 0 {
+            return Err(CompactTargetError::Negative);
+        }
+        if mantissa != 0
+            && (exponent > 34 || (mantissa > 0xff && exponent > 33) || (mantissa > 0xffff && exponent > 32))
+        {
+            return Err(CompactTargetError::Overflow);
+        }
+        let value = if exponent <= 3 {
+            U256::from_u64((mantissa >> (8 * (3 - exponent))) as u64)
+        } else {
+            U256::from_u64(mantissa as u64) << (8 * (exponent - 3))
+        };
+        if value.is_zero() {
+            return Err(CompactTargetError::Zero);
+        }
+        Ok(Target(value))
+    }
+
+    /// The normalized compact encoding; `from_compact(t.to_compact()) == t`
+    /// whenever `t` is exactly representable.
+    pub fn to_compact(self) -> u32 {
+        let mut size = self.0.bits().div_ceil(8);
+        let mut mantissa = if size <= 3 {
+            (self.0.low_u64() << (8 * (3 - size))) as u32
+        } else {
+            (self.0 >> (8 * (size - 3))).low_u64() as u32
+        };
+        // The mantissa is signed, so keep its top bit clear
+        if mantissa & 0x0080_0000 != 0 {
+            mantissa >>= 8;
+            size += 1;
+        }
+        mantissa | (size << 24)
+    }
+
+    /// Whether a block hash (in internal byte order) satisfies this target.
+    pub fn is_met_by(&self, hash: [u8; 32]) -> bool {
+        U256::from_le_bytes(hash) <= self.0
+    }
+
+    /// Difficulty relative to the difficulty-1 target.
+    pub fn difficulty(&self) -> f64 {
+        Target::MAX_ATTAINABLE.0.to_f64() / self.0.to_f64()
+    }
+
+    /// Expected number of hashes to meet this target: 2^256 / (target + 1).
+    /// Summed over a chain this is its chainwork.
+    pub fn work(&self) -> U256 {
+        if self.0 == U256::MAX {
+            return U256::ONE;
+        }
+        // 2^256 does not fit, so use (~target / (target + 1)) + 1
+        (!self.0)
+            .checked_div(self.0 + U256::ONE)
+            .expect("target + 1 is non-zero")
+            + U256::ONE
+    }
+}
+
+impl fmt::LowerHex for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target_hex(bits: u32) -> String {
+        format!("{:x}", Target::from_compact(bits).unwrap())
+    }
+
+    #[test]
+    fn test_from_compact() {
+        assert_eq!(target_hex(0x1d00ffff), "00000000ffff0000000000000000000000000000000000000000000000000000");
+        assert_eq!(target_hex(0x1f00ffff), "0000ffff00000000000000000000000000000000000000000000000000000000");
+        assert_eq!(Target::from_compact(0x01123456).unwrap().to_u256(), U256::from_u64(0x12));
+        assert_eq!(Target::from_compact(0x04123456).unwrap().to_u256(), U256::from_u64(0x12345600));
+        assert_eq!(Target::from_compact(0x05009234).unwrap().to_u256(), U256::from_u64(0x92340000));
+        assert_eq!(Target::MAX_ATTAINABLE, Target::from_compact(0x1d00ffff).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_compact() {
+        assert_eq!(Target::from_compact(0x04923456), Err(CompactTargetError::Negative));
+        assert_eq!(Target::from_compact(0xff123456), Err(CompactTargetError::Overflow));
+        assert_eq!(Target::from_compact(0x01003456), Err(CompactTargetError::Zero));
+        // A set sign bit with a zero mantissa is just zero, not negative
+        assert_eq!(Target::from_compact(0x04800000), Err(CompactTargetError::Zero));
+    }
+
+    #[test]
+    fn test_to_compact() {
+        for bits in [0x1d00ffff, 0x1f00ffff, 0x170331db, 0x02008000, 0x05009234, 0x04123456, 0x20123456] {
+            assert_eq!(Target::from_compact(bits).unwrap().to_compact(), bits);
+        }
+        // Precision beyond the mantissa is dropped, and the result is normalized
+        assert_eq!(Target::from_compact(0x01123456).unwrap().to_compact(), 0x01120000);
+        assert_eq!(Target::from_u256(U256::from_u64(0x80)).to_compact(), 0x02008000);
+    }
+
+    #[test]
+    fn test_difficulty_and_work() {
+        let genesis = Target::from_compact(0x1d00ffff).unwrap();
+        assert_eq!(genesis.difficulty(), 1.0);
+        assert_eq!(genesis.work(), U256::from_u64(0x0001_0001_0001));
+
+        let regtest = Target::from_compact(0x207fffff).unwrap();
+        assert_eq!(regtest.work(), U256::from_u64(2));
+        assert!(regtest.difficulty() < 1.0);
+    }
+
+    #[test]
+    fn test_is_met_by() {
+        let target = Target::from_compact(0x1f00ffff).unwrap();
+        let mut hash = [0xffu8; 32];
+        // Little-endian: the last bytes are the most significant
+        hash[31] = 0x00;
+        hash[30] = 0x00;
+        hash[29] = 0xff;
+        hash[28] = 0xfe;
+        assert!(target.is_met_by(hash));
+        hash[28] = 0xff;
+        assert!(!target.is_met_by(hash));
+    }
+
+    #[test]
+    fn test_u256_arithmetic() {
+        let value = U256::from_u64(u64::MAX) + U256::ONE;
+        assert_eq!(value, U256::ONE << 64);
+        assert_eq!(value >> 64, U256::ONE);
+        assert_eq!(value - U256::ONE, U256::from_u64(u64::MAX));
+        assert_eq!((U256::ONE << 200).checked_div(U256::ONE << 100), Some(U256::ONE << 100));
+        assert_eq!(U256::from_u64(100).checked_div(U256::from_u64(7)), Some(U256::from_u64(14)));
+        assert_eq!(U256::ONE.checked_div(U256::ZERO), None);
+    }
+}
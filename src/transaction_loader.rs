@@ -1,7 +1,18 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std:: fs;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use rayon::prelude::*;
 
+use crate::encoding::{deserialize, serialize, serialize_without_witness, txid_from_bytes, EncodingError, MAX_MONEY};
+use crate::script::{disassemble, parse_script_hex};
+use crate::script_type::esplora_type;
+use crate::transaction_validator::{double_sha256, script_address};
+
+/// Default directory holding one JSON transaction per file.
+pub const MEMPOOL_DIR: &str = "./mempool";
+
 /// Represents a transaction input.
 #[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
 pub struct Vin {
@@ -20,12 +31,23 @@ pub struct Vin {
     #[serde(default)]
     pub inner_witnessscript_asm: String,
 }
+
+impl Vin {
+    /// True when the input spends the null outpoint (all-zero txid, vout 0xffffffff),
+    /// which only a coinbase input may do.
+    pub fn has_null_outpoint(&self) -> bool {
+        self.vout == u32::MAX && self.txid.len() == 64 && self.txid.bytes().all(|digit| digit == b'0')
+    }
+}
+
 /// Represents the previous output of a transaction input.
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone)]
 pub struct Prevout {
     pub scriptpubkey: String,
     pub scriptpubkey_asm: String,
     pub scriptpubkey_type: String,
+    /// Left out by esplora for outputs without an address, such as OP_RETURN.
+    #[serde(default)]
     pub scriptpubkey_address: String,
     pub value: i64,
 }
@@ -36,6 +58,8 @@ pub struct Vout {
     pub scriptpubkey: String,
     pub scriptpubkey_asm: String,
     pub scriptpubkey_type: String,
+    /// Left out by esplora for outputs without an address, such as OP_RETURN.
+    #[serde(default)]
     pub scriptpubkey_address: String,
     pub value: i64,
 }
@@ -51,47 +75,369 @@ pub struct Transaction {
     pub is_coinbase: bool,
 }
 
+impl Transaction {
+    /// True when the transaction is a coinbase by consensus: a single input spending the
+    /// null outpoint.
+    pub fn has_coinbase_input(&self) -> bool {
+        self.vin.len() == 1 && self.vin[0].has_null_outpoint()
+    }
+
+    /// True when any input carries a non-empty witness stack.
+    pub fn has_witness(&self) -> bool {
+        self.vin
+            .iter()
+            .any(|vin| vin.witness.as_ref().is_some_and(|witness| !witness.is_empty()))
+    }
+
+    /// Transaction id: double SHA-256 of the serialization without witness, in display order.
+    pub fn txid(&self) -> Result<String, EncodingError> {
+        Ok(txid_from_bytes(&double_sha256(&serialize_without_witness(self)?)))
+    }
+
+    /// Witness transaction id: double SHA-256 of the full serialization, in display order.
+    /// Equal to the txid for transactions without witness data.
+    pub fn wtxid(&self) -> Result<String, EncodingError> {
+        Ok(txid_from_bytes(&double_sha256(&serialize(self)?)))
+    }
+
+    /// Size in bytes without witness data.
+    pub fn base_size(&self) -> Result<usize, EncodingError> {
+        Ok(serialize_without_witness(self)?.len())
+    }
+
+    /// Size in bytes including witness data.
+    pub fn total_size(&self) -> Result<usize, EncodingError> {
+        Ok(serialize(self)?.len())
+    }
+
+    /// BIP141 weight: base size * 3 + total size.
+    pub fn weight(&self) -> Result<usize, EncodingError> {
+        Ok(self.base_size()? * 3 + self.total_size()?)
+    }
+
+    /// Virtual size: weight / 4, rounded up.
+    pub fn vsize(&self) -> Result<usize, EncodingError> {
+        Ok(self.weight()?.div_ceil(4))
+    }
+}
+
+/// Total prevout value and total output value, or `None` if either sum overflows.
+pub fn value_totals(transaction: &Transaction) -> Option<(i64, i64)> {
+    let input_value = transaction.vin.iter().try_fold(0i64, |total, vin| total.checked_add(vin.prevout.value))?;
+    let output_value = transaction.vout.iter().try_fold(0i64, |total, vout| total.checked_add(vout.value))?;
+    Some((input_value, output_value))
+}
+
 //calculate transaction fee
 pub fn calculate_transaction_fee(transaction: &Transaction) -> i64 {
-    // Calculate total input fee
-    let input_fee: i64 = transaction
-        .vin
+    // Subtract output value from input value to get transaction fee
+    match value_totals(transaction) {
+        Some((input_value, output_value)) if input_value >= output_value => input_value - output_value,
+        // Transaction is invalid: input value is less than output value, or a total overflows
+        _ => -1,
+    }
+}
+// The script a Vin's `inner_witnessscript_asm` describes: the last witness item, or for a
+// taproot script path spend the item before the control block (and annex, if any)
+fn inner_witness_script(vin: &Vin) -> Option<Vec<u8>> {
+    let witness = vin.witness.as_deref().unwrap_or_default();
+    let mut items = witness.iter().map(hex::decode).collect::<Result<Vec<_>, _>>().ok()?;
+    if vin.prevout.scriptpubkey_type == "v1_p2tr" {
+        if items.len() >= 2 && items.last().is_some_and(|last| last.first() == Some(&0x50)) {
+            items.pop();
+        }
+        items.pop();
+    }
+    items.pop()
+}
+
+/// The first `*_asm` field that does not match the disassembly of its script hex, if any.
+/// The asm is display data from the source; a mismatch means the JSON was edited or corrupted.
+pub fn asm_mismatch(transaction: &Transaction) -> Option<String> {
+    let differs = |asm: &str, script: &str| hex::decode(script).map_or(true, |script| disassemble(&script) != asm);
+    for (index, vin) in transaction.vin.iter().enumerate() {
+        if differs(&vin.scriptsig_asm, &vin.scriptsig) {
+            return Some(format!("vin[{}].scriptsig_asm", index));
+        }
+        if differs(&vin.prevout.scriptpubkey_asm, &vin.prevout.scriptpubkey) {
+            return Some(format!("vin[{}].prevout.scriptpubkey_asm", index));
+        }
+        if !vin.inner_redeemscript_asm.is_empty() {
+            let redeem_script = parse_script_hex(&vin.scriptsig)
+                .ok()
+                .and_then(|instructions| instructions.last()?.push_data().map(<[u8]>::to_vec));
+            if redeem_script.is_none_or(|script| disassemble(&script) != vin.inner_redeemscript_asm) {
+                return Some(format!("vin[{}].inner_redeemscript_asm", index));
+            }
+        }
+        if !vin.inner_witnessscript_asm.is_empty()
+            && inner_witness_script(vin).is_none_or(|script| disassemble(&script) != vin.inner_witnessscript_asm)
+        {
+            return Some(format!("vin[{}].inner_witnessscript_asm", index));
+        }
+    }
+    transaction
+        .vout
         .iter()
-        .map(|vin| vin.prevout.value)
-        .sum();
+        .position(|vout| differs(&vout.scriptpubkey_asm, &vout.scriptpubkey))
+        .map(|index| format!("vout[{}].scriptpubkey_asm", index))
+}
 
-    // Calculate total output fee
-    let output_fee: i64 = transaction
+/// The first `scriptpubkey_type` label that is not what `esplora_type` derives from the
+/// script bytes, if any. Validation dispatches on these labels, so they must not lie.
+pub fn label_mismatch(transaction: &Transaction) -> Option<String> {
+    let differs = |label: &str, script: &str| hex::decode(script).map_or(true, |script| esplora_type(&script) != label);
+    // A coinbase input has no prevout to label; the JSON `is_coinbase` flags are not trusted
+    let coinbase = transaction.has_coinbase_input();
+    let mislabeled_input = transaction
+        .vin
+        .iter()
+        .position(|vin| !coinbase && differs(&vin.prevout.scriptpubkey_type, &vin.prevout.scriptpubkey));
+    if let Some(index) = mislabeled_input {
+        return Some(format!("vin[{}].prevout.scriptpubkey_type", index));
+    }
+    transaction
         .vout
         .iter()
-        .map(|vout| vout.value)
-        .sum();
-
-    // Subtract output fee from input fee to get transaction fee
-    if input_fee >= output_fee {
-        input_fee - output_fee
-    } else {
-        -1 // Transaction is invalid, input fee is less than output fee
-    }
-}
-pub fn fetch_transactions_from_mempool() -> Vec<Transaction> {
-    let transactions: Vec<Transaction> = fs::read_dir("./mempool")
-        .unwrap()
-        .par_bridge() // parallel processing
-        .filter_map(|entry| {
-            if let Ok(entry) = entry {
-                if let Ok(json_data) = fs::read_to_string(entry.path()) {
-                    serde_json::from_str::<Transaction>(&json_data).ok()
-                } else {
-                    None
-                }
-            } else {
-                None
+        .position(|vout| differs(&vout.scriptpubkey_type, &vout.scriptpubkey))
+        .map(|index| format!("vout[{}].scriptpubkey_type", index))
+}
+
+/// Why a mempool entry was not loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// The file or line could not be read or decoded as a transaction.
+    Parse(String),
+    /// Input `index` spends an outpoint missing from the prevout file.
+    MissingPrevout { index: usize },
+    /// The outputs are worth more than the inputs.
+    NegativeFee,
+    /// The input or output values add up to more than an `i64` holds.
+    ValueOverflow,
+    /// The named amount is outside 0..=`MAX_MONEY`.
+    AmountOutOfRange { field: String, value: i64 },
+    /// Input `index` spends the null outpoint but the transaction is not a coinbase.
+    NullPrevout { index: usize },
+    /// The transaction cannot be serialized, so it has no txid or wtxid.
+    Encoding(EncodingError),
+    /// The named `*_asm` field does not match its script hex.
+    AsmMismatch(String),
+    /// The named `scriptpubkey_type` does not match its script.
+    LabelMismatch(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Parse(message) => write!(f, "{}", message),
+            LoadError::MissingPrevout { index } => write!(f, "vin[{}] spends an unknown prevout", index),
+            LoadError::NegativeFee => write!(f, "outputs are worth more than inputs"),
+            LoadError::ValueOverflow => write!(f, "input or output values overflow"),
+            LoadError::AmountOutOfRange { field, value } => write!(f, "{} {} is outside 0..={}", field, value, MAX_MONEY),
+            LoadError::NullPrevout { index } => write!(f, "vin[{}] spends the null outpoint outside a coinbase", index),
+            LoadError::Encoding(error) => write!(f, "cannot serialize transaction: {}", error),
+            LoadError::AsmMismatch(field) => write!(f, "{} does not match its script", field),
+            LoadError::LabelMismatch(field) => write!(f, "{} does not match its script", field),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// One transaction in the mempool directory and whether it was loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MempoolEntry {
+    /// The file name, followed by `:<line>` for raw hex.
+    pub source: String,
+    /// `None` only when the entry could not be parsed.
+    pub transaction: Option<Transaction>,
+    pub error: Option<LoadError>,
+}
+
+impl MempoolEntry {
+    fn parsed(source: String, transaction: Transaction, check: impl Fn(&Transaction) -> Result<(), LoadError>) -> MempoolEntry {
+        let error = check(&transaction).err();
+        MempoolEntry { source, transaction: Some(transaction), error }
+    }
+
+    fn unparsable(source: String, error: LoadError) -> MempoolEntry {
+        MempoolEntry { source, transaction: None, error: Some(error) }
+    }
+
+    /// The transaction, if it was parsed and passed every load check.
+    pub fn loaded(&self) -> Option<&Transaction> {
+        self.transaction.as_ref().filter(|_| self.error.is_none())
+    }
+}
+
+// The checks every mempool format applies once a transaction is parsed
+fn check_loaded(transaction: &Transaction) -> Result<(), LoadError> {
+    // Everything downstream hashes loaded transactions, so they must serialize
+    transaction.txid().and(transaction.wtxid()).map_err(LoadError::Encoding)?;
+    // Such an input has no prevout to check it against (Core's bad-txns-prevout-null)
+    if !transaction.has_coinbase_input() {
+        if let Some(index) = transaction.vin.iter().position(Vin::has_null_outpoint) {
+            return Err(LoadError::NullPrevout { index });
+        }
+    }
+    check_amounts(transaction)?;
+    if value_totals(transaction).is_none() {
+        return Err(LoadError::ValueOverflow);
+    }
+    if calculate_transaction_fee(transaction) == -1 {
+        return Err(LoadError::NegativeFee);
+    }
+    match label_mismatch(transaction) {
+        Some(field) => Err(LoadError::LabelMismatch(field)),
+        None => Ok(()),
+    }
+}
+
+// Every prevout and output amount must be in 0..=MAX_MONEY, as raw outputs are when decoded
+fn check_amounts(transaction: &Transaction) -> Result<(), LoadError> {
+    let prevouts = transaction.vin.iter().enumerate().map(|(index, vin)| (format!("vin[{}].prevout.value", index), vin.prevout.value));
+    let outputs = transaction.vout.iter().enumerate().map(|(index, vout)| (format!("vout[{}].value", index), vout.value));
+    match prevouts.chain(outputs).find(|(_, value)| !(0..=MAX_MONEY).contains(value)) {
+        Some((field, value)) => Err(LoadError::AmountOutOfRange { field, value }),
+        None => Ok(()),
+    }
+}
+
+// JSON transactions also carry asm fields, which must match their scripts
+fn check_loaded_json(transaction: &Transaction) -> Result<(), LoadError> {
+    check_loaded(transaction)?;
+    match asm_mismatch(transaction) {
+        Some(field) => Err(LoadError::AsmMismatch(field)),
+        None => Ok(()),
+    }
+}
+
+/// Reads every file in `dir` as an esplora-style JSON transaction, in file name order.
+///
+/// Transactions whose fee is negative, whose asm fields disagree with their script hex,
+/// or whose script types are mislabeled are parsed but not loaded.
+pub fn load_mempool_entries(dir: &str) -> std::io::Result<Vec<MempoolEntry>> {
+    let paths = sorted_paths(dir)?;
+    let entries: Vec<MempoolEntry> = paths
+        .par_iter()
+        .map(|path| {
+            let source = file_name(path);
+            let parsed = fs::read_to_string(path)
+                .map_err(|error| LoadError::Parse(error.to_string()))
+                .and_then(|json_data| serde_json::from_str::<Transaction>(&json_data).map_err(|error| LoadError::Parse(error.to_string())));
+            match parsed {
+                Ok(transaction) => MempoolEntry::parsed(source, transaction, check_loaded_json),
+                Err(error) => MempoolEntry::unparsable(source, error),
             }
         })
-        .filter(|transaction| calculate_transaction_fee(transaction) != -1)
         .collect();
+    Ok(entries)
+}
+
+// Directory entries in name order, so loading is deterministic. This is the loaders' only
+// I/O error: a file that cannot be read or parsed is recorded in its entry instead.
+fn sorted_paths(dir: &str) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = fs::read_dir(dir)?.map(|entry| entry.map(|entry| entry.path())).collect::<std::io::Result<Vec<_>>>()?;
+    paths.sort();
+    Ok(paths)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// A spent output listed in the prevout file that accompanies raw transactions.
+///
+/// Only the script and value are given; the asm, type and address are derived from the
+/// script, as they are for raw outputs.
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
+pub struct PrevoutEntry {
+    pub txid: String,
+    pub vout: u32,
+    pub scriptpubkey: String,
+    pub value: i64,
+}
+
+impl PrevoutEntry {
+    /// The spent output with its derived fields filled in, or `None` if the script is not hex.
+    pub fn prevout(&self) -> Option<Prevout> {
+        let script = hex::decode(&self.scriptpubkey).ok()?;
+        Some(Prevout {
+            scriptpubkey_asm: disassemble(&script),
+            scriptpubkey_type: esplora_type(&script).to_string(),
+            scriptpubkey_address: script_address(&script),
+            scriptpubkey: self.scriptpubkey.clone(),
+            value: self.value,
+        })
+    }
+}
+
+/// Spent outputs keyed by outpoint (`txid`, `vout`).
+pub type PrevoutMap = HashMap<(String, u32), Prevout>;
+
+/// Reads a JSON array of `PrevoutEntry` values into a lookup map. Fails if any
+/// `scriptpubkey` is not hex.
+pub fn load_prevouts(path: &Path) -> std::io::Result<PrevoutMap> {
+    let json_data = fs::read_to_string(path)?;
+    let entries: Vec<PrevoutEntry> = serde_json::from_str(&json_data)?;
+    entries
+        .into_iter()
+        .map(|entry| {
+            let prevout = entry.prevout().ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, format!("invalid scriptpubkey hex for {}:{}", entry.txid, entry.vout))
+            })?;
+            Ok(((entry.txid, entry.vout), prevout))
+        })
+        .collect()
+}
+
+/// Decodes one raw transaction and attaches the prevout of every input.
+///
+/// Fails when the hex is malformed or an input spends an outpoint missing from
+/// `prevouts`. Inputs spending the null outpoint have no prevout and keep an empty one.
+pub fn decode_raw_transaction(raw_hex: &str, prevouts: &PrevoutMap) -> Result<Transaction, LoadError> {
+    let bytes = hex::decode(raw_hex.trim()).map_err(|error| LoadError::Parse(format!("invalid hex: {}", error)))?;
+    let mut transaction: Transaction = deserialize(&bytes).map_err(|error| LoadError::Parse(error.to_string()))?;
+    for (index, vin) in transaction.vin.iter_mut().enumerate().filter(|(_, vin)| !vin.has_null_outpoint()) {
+        vin.prevout = prevouts.get(&(vin.txid.clone(), vin.vout)).ok_or(LoadError::MissingPrevout { index })?.clone();
+    }
+    Ok(transaction)
+}
+
+/// Reads raw transaction hex from every file in `dir`, one transaction per line, in
+/// file name and line order.
+///
+/// Output and prevout types come from the script bytes; transactions with a negative fee
+/// are parsed but not loaded.
+pub fn load_raw_hex_entries(dir: &str, prevouts: &PrevoutMap) -> std::io::Result<Vec<MempoolEntry>> {
+    let paths = sorted_paths(dir)?;
+    let entries: Vec<MempoolEntry> = paths
+        .par_iter()
+        .flat_map_iter(|path| {
+            let name = file_name(path);
+            let contents = match fs::read_to_string(path) {
+                Ok(contents) => contents,
+                Err(error) => return vec![MempoolEntry::unparsable(name, LoadError::Parse(error.to_string()))],
+            };
+            contents
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(number, line)| {
+                    let source = format!("{}:{}", name, number + 1);
+                    match decode_raw_transaction(line, prevouts) {
+                        Ok(transaction) => MempoolEntry::parsed(source, transaction, check_loaded),
+                        Err(error) => MempoolEntry::unparsable(source, error),
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    Ok(entries)
+}
 
+/// Sorts transactions highest fee first.
+pub fn sort_by_fee(transactions: Vec<Transaction>) -> Vec<Transaction> {
     // Sort transactions in descending order of transaction fees
     let mut sorted_transactions = transactions;
     sorted_transactions.par_sort_unstable_by_key(|transaction| {
@@ -101,13 +447,132 @@ pub fn fetch_transactions_from_mempool() -> Vec<Transaction> {
 
     sorted_transactions
 }
+/// Reads `mempool/<file_name>.json`, for tests that work on real mempool transactions.
+#[cfg(test)]
+pub(crate) fn load_json_transaction(file_name: &str) -> Transaction {
+    let json_data = fs::read_to_string(format!("{}/{}.json", MEMPOOL_DIR, file_name)).unwrap();
+    serde_json::from_str(&json_data).unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::encoding::serialize;
+    use sha2::Digest;
+
+    fn prevouts_of(transactions: &[Transaction]) -> PrevoutMap {
+        transactions
+            .iter()
+            .flat_map(|transaction| &transaction.vin)
+            .map(|vin| ((vin.txid.clone(), vin.vout), vin.prevout.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_raw_transaction() {
+        let transaction = load_json_transaction("0183b7cc0d270638fc8d51be094a76ada65b7f5f2044512ed42437f97fa999ac");
+        let raw_hex = hex::encode(serialize(&transaction).unwrap());
+        let prevouts = prevouts_of(std::slice::from_ref(&transaction));
+
+        let decoded = decode_raw_transaction(&raw_hex, &prevouts).unwrap();
+        assert_eq!(decoded.version, transaction.version);
+        assert_eq!(decoded.locktime, transaction.locktime);
+        assert_eq!(decoded.vin[0].txid, transaction.vin[0].txid);
+        assert_eq!(decoded.vin[0].scriptsig, transaction.vin[0].scriptsig);
+        assert_eq!(decoded.vin[0].prevout, transaction.vin[0].prevout);
+        assert_eq!(decoded.vout[0].scriptpubkey, transaction.vout[0].scriptpubkey);
+        // Asm and type are derived from the script bytes
+        assert_eq!(decoded.vin[0].scriptsig_asm, transaction.vin[0].scriptsig_asm);
+        assert_eq!(decoded.vout[0].scriptpubkey_asm, transaction.vout[0].scriptpubkey_asm);
+        assert_eq!(decoded.vout[0].scriptpubkey_type, transaction.vout[0].scriptpubkey_type);
+        assert_eq!(calculate_transaction_fee(&decoded), calculate_transaction_fee(&transaction));
+
+        // Inputs without a known prevout cannot be loaded
+        assert_eq!(decode_raw_transaction(&raw_hex, &PrevoutMap::new()), Err(LoadError::MissingPrevout { index: 0 }));
+    }
+
+    #[test]
+    fn test_null_prevout_outside_coinbase() {
+        // Two inputs, the second spending the null outpoint as a coinbase would
+        let mut transaction = load_json_transaction("0018c221bca3da35128baabe412a14c95b6864b2e6f7f7a8ffdd8eb0923dec49");
+        let mut null_input = transaction.vin[0].clone();
+        null_input.txid = "0".repeat(64);
+        null_input.vout = u32::MAX;
+        transaction.vin.push(null_input);
+        let raw_hex = hex::encode(serialize(&transaction).unwrap());
+        let prevouts = prevouts_of(&[transaction.clone()]);
+
+        let decoded = decode_raw_transaction(&raw_hex, &prevouts).unwrap();
+        assert!(!decoded.is_coinbase && !decoded.vin[1].is_coinbase);
+        assert_eq!(decoded.vin[1].prevout, Prevout::default());
+        assert_eq!(check_loaded(&decoded), Err(LoadError::NullPrevout { index: 1 }));
+        assert_eq!(check_loaded(&transaction), Err(LoadError::NullPrevout { index: 1 }));
+    }
+
+    #[test]
+    fn test_load_raw_hex_entries() {
+        let transactions = vec![
+            load_json_transaction("0183b7cc0d270638fc8d51be094a76ada65b7f5f2044512ed42437f97fa999ac"),
+            load_json_transaction("00d12b523d8b7ad90e2269767478764c243625539dc59bcd457d14ca1aa4e38c"),
+        ];
+        let dir = std::env::temp_dir().join(format!("raw_mempool_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // Two transactions on separate lines of one file, plus a blank line
+        let lines: Vec<String> = transactions
+            .iter()
+            .map(|transaction| hex::encode(serialize(transaction).unwrap()))
+            .collect();
+        fs::write(dir.join("transactions.hex"), format!("{}\n\n{}\n", lines[0], lines[1])).unwrap();
+
+        let entries: Vec<PrevoutEntry> = prevouts_of(&transactions)
+            .into_iter()
+            .map(|((txid, vout), prevout)| PrevoutEntry { txid, vout, scriptpubkey: prevout.scriptpubkey, value: prevout.value })
+            .collect();
+        let prevout_path = std::env::temp_dir().join(format!("raw_prevouts_{}.json", std::process::id()));
+        fs::write(&prevout_path, serde_json::to_string(&entries).unwrap()).unwrap();
+
+        let prevouts = load_prevouts(&prevout_path).unwrap();
+        let entries = load_raw_hex_entries(dir.to_str().unwrap(), &prevouts).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_file(&prevout_path).unwrap();
+
+        // Entries are named by file and line
+        let sources: Vec<&str> = entries.iter().map(|entry| entry.source.as_str()).collect();
+        assert_eq!(sources, ["transactions.hex:1", "transactions.hex:3"]);
+        assert!(entries.iter().all(|entry| entry.loaded().is_some()));
+        // The prevout file gives only script and value; the rest matches esplora's JSON
+        let first = entries[0].loaded().unwrap();
+        for (loaded, original) in first.vin.iter().zip(&transactions[0].vin) {
+            assert_eq!(loaded.prevout, original.prevout);
+        }
+        assert_eq!(first.vout, transactions[0].vout);
+        let loaded = sort_by_fee(entries.iter().filter_map(MempoolEntry::loaded).cloned().collect());
+        assert_eq!(loaded.len(), 2);
+        assert!(calculate_transaction_fee(&loaded[0]) >= calculate_transaction_fee(&loaded[1]));
+    }
+
+    #[test]
+    fn test_txid_and_wtxid() {
+        let file_name = "0018c221bca3da35128baabe412a14c95b6864b2e6f7f7a8ffdd8eb0923dec49";
+        let transaction = load_json_transaction(file_name);
+
+        let txid = transaction.txid().unwrap();
+        assert_eq!(txid, "6494cdb062c4a1bc3e74760f9fd389c69c9cd0dcba246c0c58ab37688671edbd");
+        // Mempool files are named after SHA-256 of the txid bytes
+        assert_eq!(hex::encode(sha2::Sha256::digest(hex::decode(&txid).unwrap())), file_name);
+        assert_eq!(
+            transaction.wtxid().unwrap(),
+            "1201e0d903db3b2e8551935be785440f5714b30ad7f1421b86f8ba3eeb524b4b"
+        );
+        assert_eq!(transaction.weight().unwrap(), 565);
+        assert_eq!(transaction.vsize().unwrap(), 142);
+    }
 
     #[test]
-    fn test_fetch_transactions_from_mempool() {
-        let transactions = fetch_transactions_from_mempool();
+    fn test_load_mempool_entries() {
+        let entries = load_mempool_entries(MEMPOOL_DIR).unwrap();
+        let transactions = sort_by_fee(entries.iter().filter_map(MempoolEntry::loaded).cloned().collect());
        
         //Print the first transaction for verification
         if let Some(first_transaction) = transactions.first() {
@@ -120,6 +585,96 @@ mod tests {
         println!("Number of transactions loaded: {}", transactions.len());
         //Assert that transactions are not empty
         assert!(!transactions.is_empty());
+        // Every file has an entry, loaded or not
+        assert_eq!(entries.len(), fs::read_dir(MEMPOOL_DIR).unwrap().count());
+    }
+
+    #[test]
+    fn test_load_op_return_output() {
+        // The OP_RETURN output has no scriptpubkey_address field
+        let txid = "017993a44e612f4d034c63ee5b3586800498e267525ab935eab0675f3fbd3add";
+        let transaction = load_json_transaction(txid);
+        assert_eq!(transaction.vout[2].scriptpubkey_type, "op_return");
+        assert_eq!(transaction.vout[2].scriptpubkey_address, "");
+        assert_eq!(check_loaded_json(&transaction), Ok(()));
+
+        let entries = load_mempool_entries(MEMPOOL_DIR).unwrap();
+        let entry = entries.iter().find(|entry| entry.source == format!("{}.json", txid)).unwrap();
+        assert_eq!(entry.loaded(), Some(&transaction));
+    }
+
+    #[test]
+    fn test_unserializable_transaction() {
+        // A bad witness item only breaks the wtxid
+        let mut transaction = load_json_transaction("0018c221bca3da35128baabe412a14c95b6864b2e6f7f7a8ffdd8eb0923dec49");
+        transaction.vin[0].witness.as_mut().unwrap()[0] = "zz".to_string();
+        assert!(matches!(check_loaded(&transaction), Err(LoadError::Encoding(EncodingError::InvalidHex { field: "witness", .. }))));
+
+        let mut transaction = load_json_transaction("0018c221bca3da35128baabe412a14c95b6864b2e6f7f7a8ffdd8eb0923dec49");
+        transaction.vin[0].scriptsig = "0".to_string();
+        assert!(matches!(check_loaded(&transaction), Err(LoadError::Encoding(EncodingError::InvalidHex { field: "scriptsig", .. }))));
+    }
+
+    #[test]
+    fn test_amounts_in_range() {
+        let mut transaction = load_json_transaction("0018c221bca3da35128baabe412a14c95b6864b2e6f7f7a8ffdd8eb0923dec49");
+        assert_eq!(check_loaded(&transaction), Ok(()));
+
+        // A negative output would otherwise inflate the fee paid to the coinbase
+        transaction.vout[0].value = -1;
+        assert_eq!(check_loaded(&transaction), Err(LoadError::AmountOutOfRange { field: "vout[0].value".to_string(), value: -1 }));
+        transaction.vout[0].value = 0;
+        transaction.vin[0].prevout.value = MAX_MONEY + 1;
+        assert_eq!(
+            check_loaded(&transaction),
+            Err(LoadError::AmountOutOfRange { field: "vin[0].prevout.value".to_string(), value: MAX_MONEY + 1 })
+        );
+
+        // In-range amounts can still add up past i64::MAX
+        transaction.vin[0].prevout.value = MAX_MONEY;
+        transaction.vout = vec![transaction.vout[0].clone(); 5000];
+        for vout in &mut transaction.vout {
+            vout.value = MAX_MONEY;
+        }
+        assert_eq!(check_loaded(&transaction), Err(LoadError::ValueOverflow));
+        assert_eq!(calculate_transaction_fee(&transaction), -1);
+    }
+
+    #[test]
+    fn test_asm_mismatch() {
+        // P2SH-P2WSH multisig inputs: scriptsig, prevout, redeem script and witness script asm are all checked
+        let json = fs::read_to_string("mempool/0d9ef76964c23e940ebcddde868c1089dfdb52147364da01ee92438dfb7c9375.json").unwrap();
+        let mut transaction: Transaction = serde_json::from_str(&json).unwrap();
+        assert_eq!(asm_mismatch(&transaction), None);
+
+        transaction.vin[1].inner_witnessscript_asm = transaction.vin[0].inner_witnessscript_asm.clone();
+        assert_eq!(asm_mismatch(&transaction).as_deref(), Some("vin[1].inner_witnessscript_asm"));
+
+        transaction.vout[1].scriptpubkey_asm.push_str(" OP_DROP");
+        transaction.vin[1].inner_witnessscript_asm.clear();
+        assert_eq!(asm_mismatch(&transaction).as_deref(), Some("vout[1].scriptpubkey_asm"));
+    }
+
+    #[test]
+    fn test_label_mismatch() {
+        let json = fs::read_to_string("mempool/0d9ef76964c23e940ebcddde868c1089dfdb52147364da01ee92438dfb7c9375.json").unwrap();
+        let mut transaction: Transaction = serde_json::from_str(&json).unwrap();
+        assert_eq!(label_mismatch(&transaction), None);
+
+        // A P2SH prevout relabeled as P2PKH would otherwise be checked as one
+        transaction.vin[2].prevout.scriptpubkey_type = "p2pkh".to_string();
+        assert_eq!(label_mismatch(&transaction).as_deref(), Some("vin[2].prevout.scriptpubkey_type"));
+
+        // Claiming to be a coinbase in the JSON does not skip the check
+        for vin in &mut transaction.vin {
+            vin.is_coinbase = true;
+        }
+        transaction.is_coinbase = true;
+        assert_eq!(label_mismatch(&transaction).as_deref(), Some("vin[2].prevout.scriptpubkey_type"));
+
+        transaction.vin[2].prevout.scriptpubkey_type = "p2sh".to_string();
+        transaction.vout[0].scriptpubkey_type = "unknown".to_string();
+        assert_eq!(label_mismatch(&transaction).as_deref(), Some("vout[0].scriptpubkey_type"));
     }
 
     #[test]
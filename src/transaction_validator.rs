@@ -4,36 +4,59 @@ extern crate sha2;
 extern crate bech32;
 extern crate secp256k1;
 
-use secp256k1::{Secp256k1, PublicKey,Message, ecdsa::Signature, Error};
-use sha2::{Digest, Sha256};
-use ripemd::Ripemd160;
-use bech32::primitives::decode::{CheckedHrpstring, SegwitHrpstring};
-use bech32::{hrp, segwit, Hrp, Bech32m};
 use std::fmt;
 
-
-// SHA-256 hash function
-pub fn sha256(input: &[u8]) -> [u8; 32] {
-    let mut hasher = Sha256::new();
-    hasher.update(input);
-    hasher.finalize().into()
+use sha2::{Digest, Sha256};
+use bech32::{hrp, segwit};
+
+use crate::encoding::{serialize, EncodingError};
+use crate::interpreter::{verify_script, ExecutionError};
+use crate::policy::PolicyError;
+use crate::script::{parse_script_hex, Instruction, Opcode, ScriptError};
+use crate::script_type::ScriptType;
+use crate::sighash::TransactionSignatureChecker;
+use crate::transaction_loader::Transaction;
+use crate::verifier::{UnknownTypePolicy, VerifierRegistry};
+
+/// Why a transaction is left out of the block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The transaction cannot be serialized, so it cannot be hashed into a block.
+    Encoding(EncodingError),
+    /// Output `index` is not the script, or does not pay the address, its `scriptpubkey_type` names.
+    OutputScript { index: usize, script_type: String },
+    /// The prevout of input `index` is not the script, or does not pay the address, its `scriptpubkey_type` names.
+    PrevoutScript { index: usize, script_type: String },
+    /// Input `index` failed script verification.
+    Script { index: usize, error: ExecutionError },
+    /// Input `index` is valid but its witness is non-standard.
+    NonStandardWitness { index: usize, error: PolicyError },
+    /// Output `index` is of a type with no registered verifier.
+    UnknownOutputType { index: usize, script_type: String },
+    /// The prevout of input `index` is of a type with no registered verifier.
+    UnknownInputType { index: usize, script_type: String },
 }
 
-// Ripemd-160 hash function
-pub fn ripemd160(input: &[u8]) -> [u8; 20] {
-    let mut hasher = Ripemd160::new();
-    hasher.update(input);
-    hasher.finalize().into()
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::Encoding(error) => write!(f, "cannot serialize transaction: {}", error),
+            ValidationError::OutputScript { index, script_type } => {
+                write!(f, "vout[{}] does not match its {} scriptpubkey_type or address", index, script_type)
+            }
+            ValidationError::PrevoutScript { index, script_type } => {
+                write!(f, "vin[{}] prevout does not match its {} scriptpubkey_type or address", index, script_type)
+            }
+            ValidationError::Script { index, error } => write!(f, "vin[{}] script verification failed: {}", index, error),
+            ValidationError::NonStandardWitness { index, error } => write!(f, "vin[{}] witness is non-standard: {}", index, error),
+            ValidationError::UnknownOutputType { index, script_type } => write!(f, "vout[{}] has unrecognized type {}", index, script_type),
+            ValidationError::UnknownInputType { index, script_type } => write!(f, "vin[{}] spends unrecognized type {}", index, script_type),
+        }
+    }
 }
 
-// Convert a byte slice to a base58check-encoded string
-pub fn to_base58check(bytes: &[u8]) -> String {
-    let mut data = vec![0; 1 + bytes.len() + 4];
-    data[1..1 + bytes.len()].copy_from_slice(bytes);
-    let checksum = &sha256(&sha256(&data[0..1 + bytes.len()]))[0..4];
-    data[1 + bytes.len()..].copy_from_slice(checksum);
-    bs58::encode(data).into_string()
-}
+impl std::error::Error for ValidationError {}
+
 
 // Function to calculate double SHA256 hash
 pub fn double_sha256(data: &[u8]) -> [u8; 32] {
@@ -42,7 +65,17 @@ pub fn double_sha256(data: &[u8]) -> [u8; 32] {
     let result1 = hasher.finalize();
 
     let mut hasher = Sha256::new();
-    hasher.update(&result1);
+    hasher.update(result1);
+    hasher.finalize().into()
+}
+
+// BIP340 tagged hash: SHA256(SHA256(tag) || SHA256(tag) || data)
+pub fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    let mut hasher = Sha256::new();
+    hasher.update(tag_hash);
+    hasher.update(tag_hash);
+    hasher.update(data);
     hasher.finalize().into()
 }
 
@@ -51,361 +84,151 @@ pub fn base58_encode(data: &[u8]) -> String {
     bs58::encode(data).into_string()
 }
 
-pub enum Opcode {
-    OpDup,
-    OpHash160,
-    OpPushbytes20,
-    OpEqualverify,
-    OpChecksig,
+// Base58check address for a 20-byte hash with the given version byte
+fn base58check_address(version: u8, hash: &[u8]) -> String {
+    let mut payload = vec![version];
+    payload.extend_from_slice(hash);
+    let checksum = double_sha256(&payload);
+    payload.extend_from_slice(&checksum[..4]);
+    base58_encode(&payload)
 }
 
-// Implement the Display trait for Opcode
-impl fmt::Display for Opcode {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Opcode::OpDup => write!(f, "76"),
-            Opcode::OpHash160 => write!(f, "a9"),
-            Opcode::OpPushbytes20 => write!(f, "14"),
-            Opcode::OpEqualverify => write!(f, "88"),
-            Opcode::OpChecksig => write!(f, "ac"),
-        }
-    }
+// Whether a bech32/bech32m address encodes this witness version and program
+fn witness_address_matches(address: &str, version: u8, program: &[u8]) -> bool {
+    segwit::decode(address)
+        .is_ok_and(|(_hrp, decoded_version, decoded_program)| decoded_version.to_u8() == version && decoded_program == program)
 }
 
-// Function to assemble scriptPubKey bytecode
-pub fn assemble_scriptpubkey(hash160_hex: &str) -> String {
-    let op_dup = Opcode::OpDup.to_string();
-    let op_hash160 = Opcode::OpHash160.to_string();
-    let op_pushbytes_20 = Opcode::OpPushbytes20.to_string();
-    let op_equalverify = Opcode::OpEqualverify.to_string();
-    let op_checksig = Opcode::OpChecksig.to_string();
-
-    format!(
-        "{}{}{}{}{}{}",
-        op_dup, op_hash160, op_pushbytes_20, hash160_hex, op_equalverify, op_checksig
-    )
+/// The mainnet address esplora reports for a scriptPubKey of one of the address types,
+/// or an empty string for any other script.
+pub fn script_address(script: &[u8]) -> String {
+    let address = match ScriptType::classify(script) {
+        ScriptType::P2pkh => Ok(base58check_address(0x00, &script[3..23])),
+        ScriptType::P2sh => Ok(base58check_address(0x05, &script[2..22])),
+        ScriptType::P2wpkh | ScriptType::P2wsh => segwit::encode_v0(hrp::BC, &script[2..]),
+        ScriptType::P2tr => segwit::encode_v1(hrp::BC, &script[2..]),
+        _ => return String::new(),
+    };
+    address.unwrap_or_default()
 }
 
-pub fn verify_p2pkh_address(scriptpubkey_asm: &str, scriptpubkey_address: &str, scriptpubkey: &str)->bool{
-    let mut is_valid = false;
-
-    //operation with scriptpubkey_asm----------------------------------------------------------
-    let parts: Vec<&str> = scriptpubkey_asm.split_whitespace().collect();
-    // Find the index of "OP_PUSHBYTES_32"
-    let pushbytes32_index = parts.iter().position(|&x| x == "OP_PUSHBYTES_20");
-
-    // If "OP_PUSHBYTES_32" is found, extract the next element which represents the bytes
-    if let Some(index) = pushbytes32_index {
-        if let Some(bytes_hex) = parts.get(index + 1) {
-            // Convert hexadecimal string to bytes
-            let bytecode = assemble_scriptpubkey(&bytes_hex);
-
-            //println!("ScriptPubKey bytecode: {}", bytecode);
-
-            // Derive the scriptPubKey address
-            let version_prefix = [0x00]; // Mainnet version byte
-            let mut hash160_bytes =
-            hex::decode(bytes_hex).expect("Failed to decode hash160 hex string");
-            let mut payload = version_prefix.to_vec();
-            payload.append(&mut hash160_bytes);
-            let checksum = double_sha256(&payload)[..4].to_vec();
-            payload.extend_from_slice(&checksum);
-
-            let derived_scriptpubkey_address = base58_encode(&payload);
-            //println!("derived_scriptpubkey_address: {}", derived_scriptpubkey_address);
-
-            is_valid = (bytecode == scriptpubkey)&&(derived_scriptpubkey_address == scriptpubkey_address);
+// Parse a scriptPubKey, treating unparsable hex as an empty script
+fn parse_or_empty(script: &str) -> Vec<Instruction> {
+    parse_script_hex(script).unwrap_or_default()
+}
 
-        } else {
-            println!("Scriptpubkey_asm does not contain bytes after OP_PUSHBYTES_32");
+pub fn verify_p2pkh_address(scriptpubkey_address: &str, scriptpubkey: &str)->bool{
+    // OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG
+    match parse_or_empty(scriptpubkey).as_slice() {
+        [Instruction::Op(Opcode::Dup), Instruction::Op(Opcode::Hash160), Instruction::Push(Opcode::PushBytes(20), hash), Instruction::Op(Opcode::EqualVerify), Instruction::Op(Opcode::CheckSig)] => {
+            base58check_address(0x00, hash) == scriptpubkey_address
         }
-    } else {
-        println!("Scriptpubkey_asm does not contain OP_PUSHBYTES_32");
+        _ => false,
     }
-
-    return is_valid;
 }
 
-pub fn verify_v0_p2wsh_address(scriptpubkey_asm: &str, scriptpubkey_address: &str, scriptpubkey: &str)->bool{
-    
-    let mut is_valid = false;
-
-    let parts: Vec<&str> = scriptpubkey_asm.split_whitespace().collect();
-    let hash = parts[2]; // Corrected index to extract the hash
-    let derive_scriptpubkey = format!("0020{}", &hash);
-    //operation with scriptpubkey_asm----------------------------------------------------------
-    let parts: Vec<&str> = scriptpubkey_asm.split_whitespace().collect();
-    // Find the index of "OP_PUSHBYTES_32"
-    let pushbytes32_index = parts.iter().position(|&x| x == "OP_PUSHBYTES_32");
-
-    // If "OP_PUSHBYTES_32" is found, extract the next element which represents the bytes
-    if let Some(index) = pushbytes32_index {
-        if let Some(bytes_hex) = parts.get(index + 1) {
-            // Convert hexadecimal string to bytes
-            let bytes_asm = hex::decode(bytes_hex).expect("Failed to decode bytes hex");
-
-            //bech32 decoding operation of address------------------------------------------------------------------
-            let (_hrp, _version, decoded_address) = segwit::decode(scriptpubkey_address).expect("valid address");
-            
-            is_valid = (decoded_address == bytes_asm)&&(derive_scriptpubkey==scriptpubkey);
-        } else {
-            println!("Scriptpubkey_asm does not contain bytes after OP_PUSHBYTES_32");
+pub fn verify_v0_p2wsh_address(scriptpubkey_address: &str, scriptpubkey: &str)->bool{
+    // OP_0 <32 bytes>
+    match parse_or_empty(scriptpubkey).as_slice() {
+        [Instruction::Push(Opcode::PushBytes(0), _), Instruction::Push(Opcode::PushBytes(32), program)] => {
+            witness_address_matches(scriptpubkey_address, 0, program)
         }
-    } else {
-        println!("Scriptpubkey_asm does not contain OP_PUSHBYTES_32");
+        _ => false,
     }
-
-    return is_valid;
 }
 
-pub fn verify_v1_p2tr_address(scriptpubkey_asm: &str, scriptpubkey_address: &str, scriptpubkey: &str)->bool{
-
-    let mut is_valid = false;
-
-    //operation with scriptpubkey-----------------------------------------------------------
-    let scriptpubkey_bytes = hex::decode(scriptpubkey).expect("Failed to decode scriptpubkey hex");
-
-    // Address derivation
-    let pubkey_bytes = &scriptpubkey_bytes[2..34]; // Extract the 32-byte public key
-    let pubkey_hash = Ripemd160::digest(&Sha256::digest(pubkey_bytes)); // Compute RIPEMD-160(SHA-256(public key))
-    let version_byte = [0x0]; // Version byte for taproot addresses
-    let address_bytes: Vec<u8> = [version_byte.to_vec(), pubkey_hash.to_vec()].concat();
-    let address_base58_from_scriptpubkey = to_base58check(&address_bytes);
-
-    //operation with scriptpubkey_asm----------------------------------------------------------
-    let parts: Vec<&str> = scriptpubkey_asm.split_whitespace().collect();
-    // Find the index of "OP_PUSHBYTES_32"
-    let pushbytes32_index = parts.iter().position(|&x| x == "OP_PUSHBYTES_32");
-
-    // If "OP_PUSHBYTES_32" is found, extract the next element which represents the bytes
-    if let Some(index) = pushbytes32_index {
-        if let Some(bytes_hex) = parts.get(index + 1) {
-            // Convert hexadecimal string to bytes
-            let bytes_asm = hex::decode(bytes_hex).expect("Failed to decode bytes hex");
-
-            //bech32 decoding operation of address------------------------------------------------------------------
-            let (_hrp, _version, decoded_address) = segwit::decode(scriptpubkey_address).expect("valid address");
-             
-            // Perform RIPEMD-160(SHA-256(public key)) hashing
-            let pubkey_hash_asm = ripemd160(&sha256(&bytes_asm));
-
-            // Version byte for taproot addresses
-            let version_byte = [0x0];
-
-            // Concatenate version byte and pubkey hash
-            let address_bytes_asm: Vec<u8> = [version_byte.to_vec(), pubkey_hash_asm.to_vec()].concat();
-
-            // Encode the result using Base58Check encoding
-            let address_base58_from_scriptpubkey_asm = to_base58check(&address_bytes_asm);
-
-            is_valid = (address_base58_from_scriptpubkey == address_base58_from_scriptpubkey_asm)&&(decoded_address == bytes_asm);
-            //println!("Derived Address: {}", address_base58);
-        } else {
-            println!("Scriptpubkey_asm does not contain bytes after OP_PUSHBYTES_32");
+pub fn verify_v1_p2tr_address(scriptpubkey_address: &str, scriptpubkey: &str)->bool{
+    // OP_1 <32-byte output key>
+    match parse_or_empty(scriptpubkey).as_slice() {
+        [Instruction::Op(Opcode::PushNum(1)), Instruction::Push(Opcode::PushBytes(32), output_key)] => {
+            witness_address_matches(scriptpubkey_address, 1, output_key)
         }
-    } else {
-        println!("Scriptpubkey_asm does not contain OP_PUSHBYTES_32");
+        _ => false,
     }
-    return is_valid;
 }
 
-pub fn verify_v0_p2wpkh_address(scriptpubkey_asm: &str, scriptpubkey_address: &str, scriptpubkey: &str)->bool{
-
-    let mut is_valid = false;
-
-    //operation eith scriptpubkey----------------------------------------------------------
-    let scriptpubkey_bytes = hex::decode(scriptpubkey).expect("Failed to decode scriptpubkey hex");
-    let hash_bytes = &scriptpubkey_bytes[2..];
-    let hash_hex = hex::encode(hash_bytes);
-
-    //operation with scriptpubkey_asm----------------------------------------------------------
-    let parts: Vec<&str> = scriptpubkey_asm.split_whitespace().collect();
-    // Find the index of "OP_PUSHBYTES_32"
-    let pushbytes32_index = parts.iter().position(|&x| x == "OP_PUSHBYTES_20");
-
-    // If "OP_PUSHBYTES_32" is found, extract the next element which represents the bytes
-    if let Some(index) = pushbytes32_index {
-        if let Some(bytes_hex) = parts.get(index + 1) {
-            // Convert hexadecimal string to bytes
-            let bytes_asm = hex::decode(bytes_hex).expect("Failed to decode bytes hex");
-
-            //bech32 decoding operation of address------------------------------------------------------------------
-            let (_hrp, _version, decoded_address) = segwit::decode(scriptpubkey_address).expect("valid address");
-
-            is_valid = (bytes_hex == &hash_hex)&&(decoded_address == bytes_asm);
-            //println!("Derived Address: {}", address_base58);
-        } else {
-            println!("Scriptpubkey_asm does not contain bytes after OP_PUSHBYTES_32");
+pub fn verify_v0_p2wpkh_address(scriptpubkey_address: &str, scriptpubkey: &str)->bool{
+    // OP_0 <20 bytes>
+    match parse_or_empty(scriptpubkey).as_slice() {
+        [Instruction::Push(Opcode::PushBytes(0), _), Instruction::Push(Opcode::PushBytes(20), program)] => {
+            witness_address_matches(scriptpubkey_address, 0, program)
         }
-    } else {
-        println!("Scriptpubkey_asm does not contain OP_PUSHBYTES_32");
+        _ => false,
     }
-
-    return is_valid
 }
 
-pub fn verify_p2sh_address(scriptpubkey_asm: &str, scriptpubkey_address: &str, scriptpubkey: &str)->bool{
-
-    let mut is_valid = false;
-
-    let parts: Vec<&str> = scriptpubkey_asm.split_whitespace().collect();
-    let hash = parts[2]; // Corrected index to extract the hash
-    let derive_scriptpubkey = format!("a914{}87", &hash);
-    // Split the string by whitespace
-    let tokens: Vec<&str> = scriptpubkey_asm.split_whitespace().collect();
-
-    // Find the index of "OP_PUSHBYTES_20"
-    if let Some(index) = tokens.iter().position(|&x| x == "OP_PUSHBYTES_20") {
-        // Extract the redeem script hash from the token following "OP_PUSHBYTES_20"
-        if let Some(redeem_script_hash_hex) = tokens.get(index + 1) {
-            let redeem_script_hash_bytes = hex::decode(redeem_script_hash_hex).unwrap();
-
-            // Prepend network byte
-            let network_byte: u8 = 0x05;
-            let mut address_bytes = vec![network_byte];
-            address_bytes.extend(redeem_script_hash_bytes.iter());
-
-            // Calculate checksum
-            let checksum = double_sha256(&address_bytes);
-            address_bytes.extend_from_slice(&checksum[..4]);
-
-            // Encode with Base58Check
-            let address_from_asm = base58_encode(&address_bytes);
-            is_valid = (address_from_asm==scriptpubkey_address)&&(scriptpubkey == derive_scriptpubkey);
-        } else {
-            println!("Unable to extract redeem script hash.");
+pub fn verify_p2sh_address(scriptpubkey_address: &str, scriptpubkey: &str)->bool{
+    // OP_HASH160 <20 bytes> OP_EQUAL
+    match parse_or_empty(scriptpubkey).as_slice() {
+        [Instruction::Op(Opcode::Hash160), Instruction::Push(Opcode::PushBytes(20), hash), Instruction::Op(Opcode::Equal)] => {
+            base58check_address(0x05, hash) == scriptpubkey_address
         }
-    } else {
-        println!("OP_PUSHBYTES_20 not found.");
+        _ => false,
     }
-    
-    return is_valid;
 }
 
-pub fn verify_unknown(scriptpubkey_asm: &str, scriptpubkey: &str)->bool{
-    let parts: Vec<&str> = scriptpubkey_asm.split_whitespace().collect();
-    let hash1 = parts[2]; 
-    let hash2 = parts[4];
-    let hash3 = parts[6];
-    let derive_scriptpubkey = format!("5121{}21{}21{}53ae", &hash1, &hash2, &hash3);
-    return derive_scriptpubkey==scriptpubkey;
+// Bare m-of-n multisig, n up to 20, with compressed or uncompressed keys
+pub fn verify_multisig(scriptpubkey: &str)->bool{
+    hex::decode(scriptpubkey)
+        .is_ok_and(|script| matches!(ScriptType::classify(&script), ScriptType::Multisig { .. }))
 }
 
 //input verification
-pub fn verify_scriptsig_p2pkh(scriptsig_asm: &str,message : &str) -> bool {
-    // Parse scriptsig_asm
-    let parts: Vec<&str> = scriptsig_asm.split_whitespace().collect();
-    if parts.len() != 4 {
-        println!("Invalid scriptsig_asm format");
-        return false;
-    }
-
-    // Extract signature and public key
-    let signature_hex = parts[1];
-    let public_key_hex = parts[3];
-
-    // Convert signature and public key to bytes
-    let signature_bytes = hex::decode(signature_hex).unwrap();
-    let public_key_bytes = hex::decode(public_key_hex).unwrap();
-
-    // Create secp256k1 context
-    let secp = Secp256k1::verification_only();
-
-    // Convert signature and public key to their respective types
-    //let signature = Signature::from_der(&signature_bytes).unwrap();
-    let signature = match Signature::from_der(&signature_bytes) {
-        Ok(sig) => sig,
-        Err(_) => return false,
-    };
-    let public_key = PublicKey::from_slice(&public_key_bytes).unwrap();
-
-    // Define the message (replace this with your actual message bytes)
-    let digest = double_sha256(message.as_bytes());
-    let message = Message::from_digest(digest);
-
-
-    // Verify the signature
-    if secp.verify_ecdsa(&message,& signature,& public_key).is_ok()  {
-        return true;
-    } else {
-        return false;
-    }
+// Runs the input's scriptSig, its prevout scriptPubKey and any witness through the script interpreter
+pub fn verify_input(transaction: &Transaction, input_index: usize) -> Result<(), ExecutionError> {
+    let vin = &transaction.vin[input_index];
+    let script_sig = decode_script_hex(&vin.scriptsig)?;
+    let script_pubkey = decode_script_hex(&vin.prevout.scriptpubkey)?;
+    let witness = vin
+        .witness
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .map(|item| decode_script_hex(item))
+        .collect::<Result<Vec<_>, _>>()?;
+    verify_script(&script_sig, &script_pubkey, &witness, &TransactionSignatureChecker::new(transaction, input_index))
 }
-// pub fn verify_witness_p2wpkh(signature_code: &str , public_key_code: &str, message : &str) -> bool{
-//     let signature_bytes = hex::decode(signature_code).unwrap();
-//     let public_key_bytes = hex::decode(public_key_code).unwrap();
-//     // Create secp256k1 context
-//     let secp = Secp256k1::verification_only();s
-
-//     // Convert signature and public key to their respective types
-//     let signature = Signature::from_der(&signature_bytes).unwrap();
-//     let public_key = PublicKey::from_slice(&public_key_bytes).unwrap();
-
-//     // Define the message (replace this with your actual message bytes)
-//     let digest = double_sha256(message.as_bytes());
-//     let message = Message::from_digest(digest);
-//     // Verify the signature
-//     if secp.verify_ecdsa(&message,& signature,& public_key).is_ok()  {
-//         return true;
-//     } else {
-//         return false;
-//     }
-// }
-pub fn verify_witness_p2wpkh(signature_code: &str, public_key_code: &str, message: &str) -> bool {
-    // Decode hex strings into bytes
-    let signature_bytes = hex::decode(signature_code);
-    let public_key_bytes = hex::decode(public_key_code);
-
-    // Check if decoding was successful, if not return false
-    let (signature_bytes, public_key_bytes) = match (signature_bytes, public_key_bytes) {
-        (Ok(sig), Ok(pub_key)) => (sig, pub_key),
-        _ => return false,
-    };
-
-    // Create secp256k1 context
-    let secp = Secp256k1::verification_only();
-
-    // Convert signature and public key to their respective types
-    let signature = match Signature::from_der(&signature_bytes) {
-        Ok(sig) => sig,
-        Err(_) => return false,
-    };
 
-    let public_key = match PublicKey::from_slice(&public_key_bytes) {
-        Ok(pub_key) => pub_key,
-        Err(_) => return false,
-    };
-
-    // Calculate message digest
-    let digest = double_sha256(message.as_bytes());
-    let message = Message::from_digest_slice(&digest);
-
-    // Verify the signature
-    if let Ok(_) = secp.verify_ecdsa(&message.unwrap(), &signature, &public_key) {
-        true
-    } else {
-        false
+/// Checks that `transaction` can go into a block: it serializes, then every output and
+/// every input passes the verifiers `registry` holds for its `scriptpubkey_type`.
+///
+/// Returns the first failure found, outputs before inputs.
+pub fn validate_transaction(transaction: &Transaction, registry: &VerifierRegistry) -> Result<(), ValidationError> {
+    serialize(transaction).map_err(ValidationError::Encoding)?;
+    for (index, vout) in transaction.vout.iter().enumerate() {
+        match registry.output_verifiers(&vout.scriptpubkey_type) {
+            Some(verifiers) => {
+                for verifier in verifiers {
+                    verifier.verify_output(transaction, index)?;
+                }
+            }
+            None if registry.unknown_types == UnknownTypePolicy::Reject => {
+                return Err(ValidationError::UnknownOutputType { index, script_type: vout.scriptpubkey_type.clone() });
+            }
+            None => {}
+        }
     }
-}
-//input p2sh
-pub fn verify_scriptsig_p2sh(inner_redeemscript_asm: &str, scriptsig: &str, scriptsig_asm: &str)->bool{
-    let redeemscript_last_part = inner_redeemscript_asm.split_whitespace().last().unwrap_or("");
-    scriptsig.ends_with(redeemscript_last_part)&&scriptsig_asm.ends_with(redeemscript_last_part)
-}
-//input p2wsh
-pub fn verify_witness_p2wsh(witness_script_asm: &str, witness_field_last_string: &str)->bool{
-    let parts: Vec<&str> = witness_script_asm.split_whitespace().collect();
-    // Check if parts at index 2 and index 4 exist and extract them
-    if parts.len() >= 5 {
-        let substring_2 = parts[2];
-        let substring_4 = parts[4];
-        
-        // Check if the substrings are present in the last string of the witness field
-        if witness_field_last_string.contains(substring_2) && witness_field_last_string.contains(substring_4) {
-            return true;
+    for (index, vin) in transaction.vin.iter().enumerate() {
+        for check in registry.input_checks() {
+            check.verify_input(transaction, index)?;
+        }
+        match registry.input_verifiers(&vin.prevout.scriptpubkey_type) {
+            Some(verifiers) => {
+                for verifier in verifiers {
+                    verifier.verify_input(transaction, index)?;
+                }
+            }
+            None if registry.unknown_types == UnknownTypePolicy::Reject => {
+                return Err(ValidationError::UnknownInputType { index, script_type: vin.prevout.scriptpubkey_type.clone() });
+            }
+            None => {}
         }
     }
-    false
+    Ok(())
+}
+
+fn decode_script_hex(script: &str) -> Result<Vec<u8>, ExecutionError> {
+    hex::decode(script).map_err(|_| ExecutionError::Parse(ScriptError::InvalidHex(script.to_string())))
 }
 #[cfg(test)]
 mod tests {
@@ -414,78 +237,71 @@ mod tests {
     #[test]
     fn test_verify_p2pkh_address_valid1() {
         // Sample input data for a valid case
-        let scriptpubkey_asm = "OP_DUP OP_HASH160 OP_PUSHBYTES_20 dd6892bcbea5d56471d097923d1a81b773678d12 OP_EQUALVERIFY OP_CHECKSIG";
         let scriptpubkey_address = "1MBhcGWFxc434JW7qQQXanXAjsSjPQgJLh";
         let scriptpubkey = "76a914dd6892bcbea5d56471d097923d1a81b773678d1288ac";
 
         // Assert that the function returns true for a valid case
-        assert!(verify_p2pkh_address(scriptpubkey_asm, scriptpubkey_address, scriptpubkey));
+        assert!(verify_p2pkh_address(scriptpubkey_address, scriptpubkey));
     }
 
     #[test]
     fn test_verify_p2pkh_address_valid2() {
         // Sample input data for a valid case
-        let scriptpubkey_asm = "OP_DUP OP_HASH160 OP_PUSHBYTES_20 6406723503f32089c16ad27f7acd0d15879b811d OP_EQUALVERIFY OP_CHECKSIG";
         let scriptpubkey_address = "1A7tJ4umxSBMfcJ4Cjh9sAeMkPKkSWx4DF";
         let scriptpubkey = "76a9146406723503f32089c16ad27f7acd0d15879b811d88ac";
 
         // Assert that the function returns true for a valid case
-        assert!(verify_p2pkh_address(scriptpubkey_asm, scriptpubkey_address, scriptpubkey));
+        assert!(verify_p2pkh_address(scriptpubkey_address, scriptpubkey));
     }
 
     #[test]
     fn test_verify_v0_p2wsh_address_valid1() {
         // Sample input data for a valid case
-        let scriptpubkey_asm = "OP_0 OP_PUSHBYTES_32 b5dc65e4c0f3a2fa836d379077034f0b18e675a49d242250d328adf822da500c";
         let scriptpubkey_address = "bc1qkhwxtexq7w304qmdx7g8wq60pvvwvadyn5jzy5xn9zklsgk62qxqr8jfah";
         let scriptpubkey = "0020b5dc65e4c0f3a2fa836d379077034f0b18e675a49d242250d328adf822da500c";
 
         // Assert that the function returns true for a valid case
-        assert!(verify_v0_p2wsh_address(scriptpubkey_asm, scriptpubkey_address, scriptpubkey));
+        assert!(verify_v0_p2wsh_address(scriptpubkey_address, scriptpubkey));
     }
 
     #[test]
     fn test_verify_v0_p2wsh_address_valid2() {
         // Sample input data for a valid case
-        let scriptpubkey_asm = "OP_0 OP_PUSHBYTES_32 13bddba520865eba7745979edc231f5f4ad08e2d296db835f72cb2c384402b42";
         let scriptpubkey_address = "bc1qzw7ahffqse0t5a69j70dcgclta9dpr3d99kmsd0h9jev8pzq9dpqgpam8f";
         let scriptpubkey = "002013bddba520865eba7745979edc231f5f4ad08e2d296db835f72cb2c384402b42";
 
         // Assert that the function returns true for a valid case
-        assert!(verify_v0_p2wsh_address(scriptpubkey_asm, scriptpubkey_address, scriptpubkey));
+        assert!(verify_v0_p2wsh_address(scriptpubkey_address, scriptpubkey));
     }
 
     #[test]
     fn test_verify_v1_p2tr_address_valid1() {
         // Sample input data for a valid case
-        let scriptpubkey_asm = "OP_PUSHNUM_1 OP_PUSHBYTES_32 dcbdb702f0831950fb0780047fbe7220d48103d128ad9f67f70e98ec7550f2a6";
         let scriptpubkey_address = "bc1pmj7mwqhssvv4p7c8sqz8l0njyr2gzq739zke7elhp6vwca2s72nq02vyyd";
         let scriptpubkey = "5120dcbdb702f0831950fb0780047fbe7220d48103d128ad9f67f70e98ec7550f2a6";
 
         // Assert that the function returns true for a valid case
-        assert!(verify_v1_p2tr_address(scriptpubkey_asm, scriptpubkey_address, scriptpubkey));
+        assert!(verify_v1_p2tr_address(scriptpubkey_address, scriptpubkey));
     }
 
     #[test]
     fn test_verify_v1_p2tr_address_valid2() {
         // Sample input data for an invalid case
-        let scriptpubkey_asm = "OP_PUSHNUM_1 OP_PUSHBYTES_32 d4791f2aba1e55e57ab67b096de868d2c8a759b0033fba609a2c6c75867ed455";
         let scriptpubkey_address = "bc1p63u37246re27274k0vykm6rg6ty2wkdsqvlm5cy693k8tpn7632s8n5pdp";
         let scriptpubkey = "5120d4791f2aba1e55e57ab67b096de868d2c8a759b0033fba609a2c6c75867ed455"; 
 
         // Assert that the function returns false for an invalid case
-        assert!(verify_v1_p2tr_address(scriptpubkey_asm, scriptpubkey_address, scriptpubkey));
+        assert!(verify_v1_p2tr_address(scriptpubkey_address, scriptpubkey));
     }
 
     #[test]
     fn test_verify_v0_p2wpkh_address_valid1() {
         // Define test data with invalid scriptPubKey
-        let scriptpubkey_asm = "OP_0 OP_PUSHBYTES_20 8e56a5479728f7786a6a8fc7cc0f3535cea91b52";
         let scriptpubkey_address = "bc1q3et223uh9rmhs6n23lrucre4xh82jx6jnukpcl";
         let scriptpubkey = "00148e56a5479728f7786a6a8fc7cc0f3535cea91b52"; 
 
         // Call the function under test
-        let result = verify_v0_p2wpkh_address(scriptpubkey_asm, scriptpubkey_address, scriptpubkey);
+        let result = verify_v0_p2wpkh_address(scriptpubkey_address, scriptpubkey);
 
         // Assert the result is false
         assert!(result);
@@ -494,12 +310,11 @@ mod tests {
     #[test]
     fn test_verify_v0_p2wpkh_address_valid2() {
         // Define test data with invalid scriptPubKey
-        let scriptpubkey_asm = "OP_0 OP_PUSHBYTES_20 7a665de7a370f4c9b372ab1fae587500a5bcbdb4";
         let scriptpubkey_address = "bc1q0fn9mearwr6vnvmj4v06ukr4qzjme0d565dzcx";
         let scriptpubkey = "00147a665de7a370f4c9b372ab1fae587500a5bcbdb4"; 
 
         // Call the function under test
-        let result = verify_v0_p2wpkh_address(scriptpubkey_asm, scriptpubkey_address, scriptpubkey);
+        let result = verify_v0_p2wpkh_address(scriptpubkey_address, scriptpubkey);
 
         // Assert the result is false
         assert!(result);
@@ -508,62 +323,407 @@ mod tests {
     #[test]
     fn test_verify_p2sh_address_valid1() {
         // Sample input data for a valid case
-        let scriptpubkey_asm = "OP_HASH160 OP_PUSHBYTES_20 20756d2dd9f0cc05fe200794251642ff9e760085 OP_EQUAL";
         let scriptpubkey_address = "34eeDckhVvGkbnTzGx6qbz2AkmyV9syc8R";
         let scriptpubkey = "a91420756d2dd9f0cc05fe200794251642ff9e76008587";
 
         // Assert that the function returns true for a valid case
-        assert!(verify_p2sh_address(scriptpubkey_asm, scriptpubkey_address, scriptpubkey));
+        assert!(verify_p2sh_address(scriptpubkey_address, scriptpubkey));
     }
 
     #[test]
     fn test_verify_p2sh_address_valid2() {
         // Sample input data for a valid case
-        let scriptpubkey_asm = "OP_HASH160 OP_PUSHBYTES_20 88b45cfaf3fc202f120e194d469d699158b3b8c3 OP_EQUAL";
         let scriptpubkey_address = "3E9qtVFH8AnbzJCVXRhsXP4rzuwsmzpmpL";
         let scriptpubkey = "a91488b45cfaf3fc202f120e194d469d699158b3b8c387";
 
         // Assert that the function returns true for a valid case
-        assert!(verify_p2sh_address(scriptpubkey_asm, scriptpubkey_address, scriptpubkey));
+        assert!(verify_p2sh_address(scriptpubkey_address, scriptpubkey));
     }
 
     #[test]
-    fn test_verify_unknown1() {
+    fn test_verify_multisig1() {
         // Sample input data for a valid case
-        let scriptpubkey_asm = "OP_PUSHNUM_1 OP_PUSHBYTES_33 0271cf3589a4ff65dd3daa10e1c16bb573eafa46874575bd9ed215bf0fc215824d OP_PUSHBYTES_33 02214adba0ff718eb695b55694ba4fbf921cd587f62fdba757f5b93f646af9586a OP_PUSHBYTES_33 022222222222222222222222222222222222222222222222222222222222222222 OP_PUSHNUM_3 OP_CHECKMULTISIG";
         let scriptpubkey = "51210271cf3589a4ff65dd3daa10e1c16bb573eafa46874575bd9ed215bf0fc215824d2102214adba0ff718eb695b55694ba4fbf921cd587f62fdba757f5b93f646af9586a2102222222222222222222222222222222222222222222222222222222222222222253ae";
 
         // Assert that the function returns true for a valid case
-        assert!(verify_unknown(scriptpubkey_asm, scriptpubkey));
+        assert!(verify_multisig(scriptpubkey));
     }
 
     #[test]
-    fn test_verify_unknown2() {
+    fn test_verify_multisig2() {
         // Sample input data for a valid case
-        let scriptpubkey_asm = "OP_PUSHNUM_1 OP_PUSHBYTES_33 0254de7a5999477d61249bf62e5e628b868508a32a83f5c2a6d6bc4fe55f79bccd OP_PUSHBYTES_33 0205bc4a558880b31291e37c52bbc6f99499b6d5bd2bc027a150ec1a9134f0371a OP_PUSHBYTES_33 022222222222222222222222222222222222222222222222222222222222222222 OP_PUSHNUM_3 OP_CHECKMULTISIG";
         let scriptpubkey = "51210254de7a5999477d61249bf62e5e628b868508a32a83f5c2a6d6bc4fe55f79bccd210205bc4a558880b31291e37c52bbc6f99499b6d5bd2bc027a150ec1a9134f0371a2102222222222222222222222222222222222222222222222222222222222222222253ae";
 
         // Assert that the function returns true for a valid case
-        assert!(verify_unknown(scriptpubkey_asm, scriptpubkey));
+        assert!(verify_multisig(scriptpubkey));
     }
     
     #[test]
-    fn test_verify_scriptsig_p2sh() {
-        // Test when scriptsig ends with the last part of inner_redeemscript_asm
-        let inner_redeemscript_asm = "OP_0 OP_PUSHBYTES_20 d14f30dc97f4f21f7c7a91feedd51cc018e92210";
-        let scriptsig = "160014d14f30dc97f4f21f7c7a91feedd51cc018e92210";
-        let scriptsig_asm ="OP_PUSHBYTES_22 0014d14f30dc97f4f21f7c7a91feedd51cc018e92210";
-        assert_eq!(verify_scriptsig_p2sh(inner_redeemscript_asm, scriptsig, scriptsig_asm), true);
+    fn test_verify_address_uses_script_bytes() {
+        // Truncated or non-hex scripts are rejected rather than panicking
+        assert!(!verify_p2pkh_address("1MBhcGWFxc434JW7qQQXanXAjsSjPQgJLh", "76a914dd6892bc"));
+        assert!(!verify_p2sh_address("34eeDckhVvGkbnTzGx6qbz2AkmyV9syc8R", "not hex"));
+        assert!(!verify_multisig("5121"));
+        assert!(!verify_multisig("ae"));
+
+        // The address must match the hash in the script itself
+        assert!(!verify_p2pkh_address("1A7tJ4umxSBMfcJ4Cjh9sAeMkPKkSWx4DF", "76a914dd6892bcbea5d56471d097923d1a81b773678d1288ac"));
+
+        // A v0 program of the right length is not a taproot output
+        let p2wsh_address = "bc1qkhwxtexq7w304qmdx7g8wq60pvvwvadyn5jzy5xn9zklsgk62qxqr8jfah";
+        assert!(!verify_v1_p2tr_address(p2wsh_address, "5120b5dc65e4c0f3a2fa836d379077034f0b18e675a49d242250d328adf822da500c"));
     }
 
     #[test]
-    fn test_check_substrings_presence() {
-        // Test case with substrings present in the witness field
-        let witness_script_asm = "OP_PUSHNUM_2 OP_PUSHBYTES_33 02701d42ec373c1e033c45168e848b73166573dfe5bad959febbcabc4a8853ba91 OP_PUSHBYTES_33 03038de3a46925b1bbdf705f5c3b71da5c1863e77ae6699d28d42751554536e97a OP_PUSHNUM_2 OP_CHECKMULTISIG";
-        let witness_field_last_string = "522102701d42ec373c1e033c45168e848b73166573dfe5bad959febbcabc4a8853ba912103038de3a46925b1bbdf705f5c3b71da5c1863e77ae6699d28d42751554536e97a52ae";
+    fn test_script_address() {
+        let cases = [
+            ("1MBhcGWFxc434JW7qQQXanXAjsSjPQgJLh", "76a914dd6892bcbea5d56471d097923d1a81b773678d1288ac"),
+            ("34eeDckhVvGkbnTzGx6qbz2AkmyV9syc8R", "a91420756d2dd9f0cc05fe200794251642ff9e76008587"),
+            ("bc1q3et223uh9rmhs6n23lrucre4xh82jx6jnukpcl", "00148e56a5479728f7786a6a8fc7cc0f3535cea91b52"),
+            ("bc1qkhwxtexq7w304qmdx7g8wq60pvvwvadyn5jzy5xn9zklsgk62qxqr8jfah", "0020b5dc65e4c0f3a2fa836d379077034f0b18e675a49d242250d328adf822da500c"),
+            ("bc1pmj7mwqhssvv4p7c8sqz8l0njyr2gzq739zke7elhp6vwca2s72nq02vyyd", "5120dcbdb702f0831950fb0780047fbe7220d48103d128ad9f67f70e98ec7550f2a6"),
+        ];
+        for (address, scriptpubkey) in cases {
+            assert_eq!(script_address(&hex::decode(scriptpubkey).unwrap()), address);
+        }
+        // OP_RETURN and bare multisig have no address
+        assert_eq!(script_address(&hex::decode("6a0401020304").unwrap()), "");
+        assert_eq!(script_address(&[]), "");
+    }
 
-        assert!(verify_witness_p2wsh(witness_script_asm, witness_field_last_string));
+    #[test]
+    fn test_verify_input_p2sh() {
+        // 2-of-N multisig redeem scripts and a custom 3-byte redeem script
+        for txid in [
+            "19175e830784abf518b3ca20319177fcdff20b0bc5253b984a4515e85ec116b4",
+            "3d59fd44dc9c52de1ff320e5c0813993ede5ddf25ab3149d62fb0369df35f327",
+            "b347d97e2e4edcc8f29b3f66ae8cf3855a5af2d6c2ce2d61d6f5a5089a867b40",
+        ] {
+            let json = std::fs::read_to_string(format!("mempool/{}.json", txid)).unwrap();
+            let transaction: Transaction = serde_json::from_str(&json).unwrap();
+            for (index, vin) in transaction.vin.iter().enumerate() {
+                if vin.prevout.scriptpubkey_type == "p2sh" {
+                    assert_eq!(verify_input(&transaction, index), Ok(()), "{} input {}", txid, index);
+                }
+            }
+        }
+
+        let json = std::fs::read_to_string("mempool/19175e830784abf518b3ca20319177fcdff20b0bc5253b984a4515e85ec116b4.json").unwrap();
+        let transaction: Transaction = serde_json::from_str(&json).unwrap();
+        let mut instructions = parse_script_hex(&transaction.vin[0].scriptsig).unwrap();
+
+        // A redeem script that does not hash to the prevout's hash
+        let mut tampered = transaction.clone();
+        let mut redeem_script = instructions.last().unwrap().push_data().unwrap().to_vec();
+        redeem_script[0] = 0x51;
+        *instructions.last_mut().unwrap() = Instruction::push(&redeem_script);
+        tampered.vin[0].scriptsig = hex::encode(crate::script::encode_script(&instructions));
+        assert_eq!(verify_input(&tampered, 0), Err(ExecutionError::EvalFalse));
+
+        // The scriptSig must be push-only
+        let mut tampered = transaction.clone();
+        tampered.vin[0].scriptsig = format!("61{}", transaction.vin[0].scriptsig);
+        assert_eq!(verify_input(&tampered, 0), Err(ExecutionError::SigPushOnly));
+    }
+
+    #[test]
+    fn test_verify_input_p2pkh() {
+        // Three p2pkh inputs, each signing with SIGHASH_ALL
+        let json = std::fs::read_to_string("mempool/00d7c8ddc2e75f6ba97520623390f01a910dc66a9e6a2052ee31f1b99aabdea5.json").unwrap();
+        let mut transaction: Transaction = serde_json::from_str(&json).unwrap();
+        for index in 0..transaction.vin.len() {
+            assert_eq!(verify_input(&transaction, index), Ok(()));
+        }
+
+        // Changing an output invalidates every signature
+        transaction.vout[0].value += 1;
+        assert!(verify_input(&transaction, 0).is_err());
     }
-}
 
+    #[test]
+    fn test_verify_input_p2pkh_anyonecanpay() {
+        // Both inputs sign with SIGHASH_ALL|ANYONECANPAY
+        let json = std::fs::read_to_string("mempool/204ac1129b8c7dd69d0459f57521bf88acbdeb6d57caa84a0b8a391826faf155.json").unwrap();
+        let mut transaction: Transaction = serde_json::from_str(&json).unwrap();
+        for index in 0..transaction.vin.len() {
+            assert_eq!(verify_input(&transaction, index), Ok(()));
+        }
+
+        // Dropping the other input keeps each signature valid, changing an output does not
+        let mut alone = transaction.clone();
+        alone.vin.remove(1);
+        assert_eq!(verify_input(&alone, 0), Ok(()));
+        transaction.vout[0].value -= 1;
+        assert_eq!(verify_input(&transaction, 0), Err(ExecutionError::EvalFalse));
+    }
+
+    // Spends the first output of a mempool transaction as a bare multisig signed by `signers`
+    fn spend_bare_multisig(required: usize, key_count: usize, signers: &[usize], uncompressed: bool) -> Transaction {
+        use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+        use crate::script::{encode_script, Instruction};
+        use crate::sighash::legacy_sighash;
+
+        let secp = Secp256k1::new();
+        let secret_keys: Vec<SecretKey> = (1..=key_count as u8).map(|i| SecretKey::from_slice(&[i; 32]).unwrap()).collect();
+        let public_keys: Vec<Vec<u8>> = secret_keys
+            .iter()
+            .map(|secret_key| {
+                let public_key = PublicKey::from_secret_key(&secp, secret_key);
+                if uncompressed { public_key.serialize_uncompressed().to_vec() } else { public_key.serialize().to_vec() }
+            })
+            .collect();
+        let count = |number: usize| match number {
+            1..=16 => Instruction::Op(Opcode::PushNum(number as u8)),
+            _ => Instruction::push(&[number as u8]),
+        };
+        let mut script_pubkey = vec![count(required)];
+        script_pubkey.extend(public_keys.iter().map(|key| Instruction::push(key)));
+        script_pubkey.extend([count(key_count), Instruction::Op(Opcode::CheckMultiSig)]);
+        let script_pubkey = encode_script(&script_pubkey);
+
+        let json = std::fs::read_to_string("mempool/00d12b523d8b7ad90e2269767478764c243625539dc59bcd457d14ca1aa4e38c.json").unwrap();
+        let mut transaction: Transaction = serde_json::from_str(&json).unwrap();
+        transaction.vin[0].prevout.scriptpubkey = hex::encode(&script_pubkey);
+        transaction.vin[0].prevout.scriptpubkey_type = "unknown".to_string();
+
+        let digest = legacy_sighash(&transaction, 0, &script_pubkey, 1).unwrap();
+        let mut script_sig = vec![Instruction::push(&[])];
+        for &signer in signers {
+            let signature = secp.sign_ecdsa(&Message::from_digest(digest), &secret_keys[signer]);
+            script_sig.push(Instruction::push(&[signature.serialize_der().as_ref(), &[0x01]].concat()));
+        }
+        transaction.vin[0].scriptsig = hex::encode(encode_script(&script_sig));
+        transaction
+    }
 
+    #[test]
+    fn test_verify_input_bare_multisig() {
+        let transaction = spend_bare_multisig(2, 3, &[0, 2], false);
+        assert!(verify_multisig(&transaction.vin[0].prevout.scriptpubkey));
+        assert_eq!(verify_input(&transaction, 0), Ok(()));
+
+        // Uncompressed keys, and counts above 16 written as pushes
+        assert_eq!(verify_input(&spend_bare_multisig(1, 2, &[1], true), 0), Ok(()));
+        let twenty = spend_bare_multisig(3, 20, &[4, 9, 19], false);
+        assert!(verify_multisig(&twenty.vin[0].prevout.scriptpubkey));
+        assert_eq!(verify_input(&twenty, 0), Ok(()));
+
+        // Signatures out of key order, too few, or by the wrong keys fail
+        assert!(verify_input(&spend_bare_multisig(2, 3, &[2, 0], false), 0).is_err());
+        assert!(verify_input(&spend_bare_multisig(2, 3, &[1], false), 0).is_err());
+        assert!(verify_input(&spend_bare_multisig(1, 1, &[], false), 0).is_err());
+    }
+
+    #[test]
+    fn test_verify_input_nested_segwit() {
+        // P2SH-P2WPKH, then P2SH-P2WSH inputs after a native P2WSH one
+        for txid in [
+            "2ea2dd442de2fc97e602e913ac2d3f23e66120f431be6537f101a7032ea789e8",
+            "15872893cd402e37308624df3d830da642030a8d5ac4aba2555f472324b3365e",
+        ] {
+            let json = std::fs::read_to_string(format!("mempool/{}.json", txid)).unwrap();
+            let mut transaction: Transaction = serde_json::from_str(&json).unwrap();
+            for index in 0..transaction.vin.len() {
+                assert_eq!(verify_input(&transaction, index), Ok(()), "{} input {}", txid, index);
+            }
+
+            let nested = transaction.vin.len() - 1;
+
+            // The amount is committed to by BIP143
+            transaction.vin[nested].prevout.value += 1;
+            assert_eq!(verify_input(&transaction, nested), Err(ExecutionError::EvalFalse));
+            transaction.vin[nested].prevout.value -= 1;
+
+            // The scriptSig must be exactly the push of the witness program
+            let mut malleated = transaction.clone();
+            malleated.vin[nested].scriptsig = format!("00{}", transaction.vin[nested].scriptsig);
+            assert_eq!(verify_input(&malleated, nested), Err(ExecutionError::WitnessMalleatedP2sh));
+
+            // The witness carries the authorization
+            let mut stripped = transaction.clone();
+            stripped.vin[nested].witness = Some(Vec::new());
+            assert!(verify_input(&stripped, nested).is_err());
+        }
+    }
+
+    #[test]
+    fn test_verify_input_native_p2wsh() {
+        // A hash time-locked contract spent with a preimage, a CSV-locked key and a 2-of-2 multisig
+        for txid in [
+            "8ae7e7b9ab2bcb7eb880c9043bb908726c3e2b6a07d206ebde92f2cfeaf1a004",
+            "f047c2ed8e97766c9d622a7b322b5495069dccec4daa421528df8aa4d9c0ee7d",
+            "b3df1a40a4f9b1a9d26bc4f8a6a8aefc09691752c15fb06525f998cf0813bb62",
+        ] {
+            let json = std::fs::read_to_string(format!("mempool/{}.json", txid)).unwrap();
+            let transaction: Transaction = serde_json::from_str(&json).unwrap();
+            assert_eq!(verify_input(&transaction, 0), Ok(()), "{}", txid);
+
+            // The witness script must hash to the program
+            let mut tampered = transaction.clone();
+            let witness = tampered.vin[0].witness.as_mut().unwrap();
+            let last = witness.len() - 1;
+            witness[last].push_str("75");
+            assert_eq!(verify_input(&tampered, 0), Err(ExecutionError::WitnessProgramMismatch));
+
+            let mut stripped = transaction.clone();
+            stripped.vin[0].witness = Some(Vec::new());
+            assert_eq!(verify_input(&stripped, 0), Err(ExecutionError::WitnessProgramWitnessEmpty));
+
+            // Signatures commit to the witness script and amount
+            let mut tampered = transaction.clone();
+            tampered.vin[0].prevout.value += 1;
+            assert!(verify_input(&tampered, 0).is_err());
+        }
+    }
+
+    #[test]
+    fn test_verify_input_p2wpkh() {
+        for (txid, index) in [
+            // Spends more than 2^32 satoshis, which must not be truncated
+            ("863ee792c7701947d750e9872ee43a56e04b0c0cb7ea5f5be2dd473dd74bcf36", 0),
+            // SIGHASH_ALL|ANYONECANPAY
+            ("c56e72a48bfe6b6bbc0fcc28c920368e2abb80482bb5810afb0e242f84b8a3b6", 0),
+            // SIGHASH_SINGLE|ANYONECANPAY
+            ("c5716cfa78d5a337c0b81bc8ce87481a5ba1ff304f907367b68a5c3d2fee7a4b", 1),
+        ] {
+            let json = std::fs::read_to_string(format!("mempool/{}.json", txid)).unwrap();
+            let transaction: Transaction = serde_json::from_str(&json).unwrap();
+            assert_eq!(verify_input(&transaction, index), Ok(()), "{}", txid);
+
+            let mut tampered = transaction.clone();
+            tampered.vin[index].prevout.value += 1 << 32;
+            assert_eq!(verify_input(&tampered, index), Err(ExecutionError::EvalFalse));
+
+            // Two witness items are required
+            let mut tampered = transaction.clone();
+            tampered.vin[index].witness.as_mut().unwrap().push(String::new());
+            assert_eq!(verify_input(&tampered, index), Err(ExecutionError::WitnessProgramMismatch));
+        }
+    }
+
+    #[test]
+    fn test_verify_input_taproot_key_path() {
+        for (txid, index) in [
+            // A 64-byte signature with the default hash type
+            ("5588015c0c3e5fa905a813a4be5765e3f684dd71a43acae3c33a5c4d5c0ae213", 0),
+            // Explicit SIGHASH_ALL and SIGHASH_SINGLE|ANYONECANPAY
+            ("9abab76ff8d5381f46291bf5f6fd911df420d08ed3009b4756104c403ecbeb82", 0),
+            ("e8c0af3c2cc9ae68aae011e89284eb6bbcdc7dd6aec7802273cad17635bf0614", 1),
+        ] {
+            let json = std::fs::read_to_string(format!("mempool/{}.json", txid)).unwrap();
+            let transaction: Transaction = serde_json::from_str(&json).unwrap();
+            assert_eq!(verify_input(&transaction, index), Ok(()), "{}", txid);
+
+            // The amount spent is always signed
+            let mut tampered = transaction.clone();
+            tampered.vin[index].prevout.value += 1;
+            assert_eq!(verify_input(&tampered, index), Err(ExecutionError::SchnorrSig));
+
+            // A different hash type, or the default one written out, invalidates the signature
+            let mut tampered = transaction.clone();
+            let signature = &mut tampered.vin[index].witness.as_mut().unwrap()[0];
+            signature.truncate(128);
+            signature.push_str(if txid.starts_with("5588") { "00" } else { "02" });
+            assert_eq!(verify_input(&tampered, index), Err(ExecutionError::SchnorrSig));
+
+            let mut stripped = transaction.clone();
+            stripped.vin[index].witness = Some(Vec::new());
+            assert_eq!(verify_input(&stripped, index), Err(ExecutionError::WitnessProgramWitnessEmpty));
+        }
+    }
+
+    #[test]
+    fn test_verify_input_taproot_script_path() {
+        // <key> OP_CHECKSIG as the only leaf, then an inscription behind a signature check with one Merkle node
+        for txid in [
+            "1495b024706adbe373079fdcf09a5aab22fa65c790bbb0cb355b54af52cfac8c",
+            "0bacc356618723a367a5fd1e142625813e6df7921006f4baa87828b5e2cfa925",
+        ] {
+            let json = std::fs::read_to_string(format!("mempool/{}.json", txid)).unwrap();
+            let transaction: Transaction = serde_json::from_str(&json).unwrap();
+            assert_eq!(verify_input(&transaction, 0), Ok(()), "{}", txid);
+
+            let tamper = |item: usize, change: &dyn Fn(&mut Vec<u8>)| {
+                let mut tampered = transaction.clone();
+                let witness = &mut tampered.vin[0].witness.as_mut().unwrap()[item];
+                let mut bytes = hex::decode(&*witness).unwrap();
+                change(&mut bytes);
+                *witness = hex::encode(bytes);
+                verify_input(&tampered, 0)
+            };
+            // The leaf script, internal key, Merkle path and output key parity are all committed to
+            assert_eq!(tamper(1, &|script| script.insert(0, 0x61)), Err(ExecutionError::WitnessProgramMismatch));
+            assert_eq!(tamper(2, &|control| *control.last_mut().unwrap() ^= 1), Err(ExecutionError::WitnessProgramMismatch));
+            assert_eq!(tamper(2, &|control| control[0] ^= 1), Err(ExecutionError::WitnessProgramMismatch));
+            assert_eq!(tamper(2, &|control| control.push(0)), Err(ExecutionError::TaprootWrongControlSize));
+            // A failing signature is an error in tapscript, not a false result
+            assert_eq!(tamper(0, &|signature| signature[0] ^= 1), Err(ExecutionError::SchnorrSig));
+        }
+
+        // An inscription whose leaf has no signature check: OP_DROP OP_0 OP_IF ... OP_ENDIF OP_1
+        let json = std::fs::read_to_string("mempool/616df390dd47f1b54968e8c31a396647dd73e52d7cf69ab33fe7bf670b8dccdc.json").unwrap();
+        let transaction: Transaction = serde_json::from_str(&json).unwrap();
+        assert_eq!(verify_input(&transaction, 0), Ok(()));
+    }
+
+    #[test]
+    fn test_verify_input_taproot_annex() {
+        use secp256k1::{Keypair, Message, Secp256k1, SecretKey};
+        use crate::interpreter::ExecutionData;
+        use crate::sighash::taproot_sighash;
+
+        // Re-point a key path spend at a key we hold and sign it with an annex
+        let json = std::fs::read_to_string("mempool/5588015c0c3e5fa905a813a4be5765e3f684dd71a43acae3c33a5c4d5c0ae213.json").unwrap();
+        let mut transaction: Transaction = serde_json::from_str(&json).unwrap();
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_secret_key(&secp, &SecretKey::from_slice(&[1; 32]).unwrap());
+        transaction.vin[0].prevout.scriptpubkey = format!("5120{}", hex::encode(keypair.x_only_public_key().0.serialize()));
+
+        let annex = vec![0x50, 0xaa];
+        let execution = ExecutionData { annex: Some(annex.clone()), ..ExecutionData::default() };
+        let digest = taproot_sighash(&transaction, 0, 0x00, &execution).unwrap();
+        let signature = secp.sign_schnorr_no_aux_rand(&Message::from_digest(digest), &keypair);
+        transaction.vin[0].witness = Some(vec![hex::encode(signature.serialize()), hex::encode(&annex)]);
+        assert_eq!(verify_input(&transaction, 0), Ok(()));
+
+        // The annex is signed: changing or dropping it invalidates the signature
+        let mut tampered = transaction.clone();
+        tampered.vin[0].witness.as_mut().unwrap()[1] = "50ab".to_string();
+        assert_eq!(verify_input(&tampered, 0), Err(ExecutionError::SchnorrSig));
+        tampered.vin[0].witness.as_mut().unwrap().pop();
+        assert_eq!(verify_input(&tampered, 0), Err(ExecutionError::SchnorrSig));
+    }
+
+    #[test]
+    fn test_validate_transaction_reports_failure() {
+        let registry = VerifierRegistry::standard(true, UnknownTypePolicy::Reject);
+        // Three p2pkh inputs
+        let json = std::fs::read_to_string("mempool/00d7c8ddc2e75f6ba97520623390f01a910dc66a9e6a2052ee31f1b99aabdea5.json").unwrap();
+        let transaction: Transaction = serde_json::from_str(&json).unwrap();
+        assert_eq!(validate_transaction(&transaction, &registry), Ok(()));
+
+        let mut tampered = transaction.clone();
+        tampered.vout[0].scriptpubkey_address = transaction.vin[0].prevout.scriptpubkey_address.clone();
+        assert_eq!(
+            validate_transaction(&tampered, &registry),
+            Err(ValidationError::OutputScript { index: 0, script_type: "p2sh".to_string() })
+        );
+
+        // A failing input is reported even when the inputs after it are valid
+        let mut tampered = transaction.clone();
+        tampered.vin[1].prevout.value += 1;
+        tampered.vin[1].scriptsig = transaction.vin[0].scriptsig.clone();
+        assert!(matches!(validate_transaction(&tampered, &registry), Err(ValidationError::Script { index: 1, .. })));
+
+        // Malformed hex is an error, not a panic
+        let mut tampered = transaction.clone();
+        tampered.vin[2].scriptsig = "zz".to_string();
+        assert!(matches!(validate_transaction(&tampered, &registry), Err(ValidationError::Encoding(_))));
+        let mut tampered = transaction.clone();
+        tampered.vin[2].prevout.scriptpubkey = "76a9".to_string();
+        assert_eq!(
+            validate_transaction(&tampered, &registry),
+            Err(ValidationError::PrevoutScript { index: 2, script_type: "p2pkh".to_string() })
+        );
+    }
+}
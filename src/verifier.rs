@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use crate::policy::check_witness_standard;
+use crate::transaction_loader::Transaction;
+use crate::transaction_validator::{
+    verify_input, verify_multisig, verify_p2pkh_address, verify_p2sh_address, verify_v0_p2wpkh_address, verify_v0_p2wsh_address,
+    verify_v1_p2tr_address, ValidationError,
+};
+
+/// Checks output `index` of a transaction.
+pub trait OutputVerifier {
+    fn verify_output(&self, transaction: &Transaction, index: usize) -> Result<(), ValidationError>;
+}
+
+impl<F> OutputVerifier for F
+where
+    F: Fn(&Transaction, usize) -> Result<(), ValidationError>,
+{
+    fn verify_output(&self, transaction: &Transaction, index: usize) -> Result<(), ValidationError> {
+        self(transaction, index)
+    }
+}
+
+/// Checks input `index` of a transaction.
+pub trait InputVerifier {
+    fn verify_input(&self, transaction: &Transaction, index: usize) -> Result<(), ValidationError>;
+}
+
+impl<F> InputVerifier for F
+where
+    F: Fn(&Transaction, usize) -> Result<(), ValidationError>,
+{
+    fn verify_input(&self, transaction: &Transaction, index: usize) -> Result<(), ValidationError> {
+        self(transaction, index)
+    }
+}
+
+// Whether a scriptPubKey pays an address, as the `verify_*_address` functions check
+type AddressCheck = fn(&str, &str) -> bool;
+
+/// What to do with an output, or an input's prevout, whose `scriptpubkey_type` has no verifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownTypePolicy {
+    Accept,
+    Reject,
+}
+
+/// Output and input verifiers keyed by `scriptpubkey_type`, plus checks run on every input.
+///
+/// Several verifiers may be registered for one type; all of them must pass, in
+/// registration order.
+pub struct VerifierRegistry {
+    outputs: HashMap<String, Vec<Box<dyn OutputVerifier>>>,
+    inputs: HashMap<String, Vec<Box<dyn InputVerifier>>>,
+    input_checks: Vec<Box<dyn InputVerifier>>,
+    pub unknown_types: UnknownTypePolicy,
+}
+
+impl VerifierRegistry {
+    /// An empty registry.
+    pub fn new(unknown_types: UnknownTypePolicy) -> VerifierRegistry {
+        VerifierRegistry { outputs: HashMap::new(), inputs: HashMap::new(), input_checks: Vec::new(), unknown_types }
+    }
+
+    /// Verifiers for every type esplora labels, as `validation_transaction` uses them:
+    ///
+    /// - outputs of an address type must pay their `scriptpubkey_address`, `unknown` outputs must be
+    ///   bare multisig, and `p2pk` and `op_return` outputs are accepted
+    /// - inputs spending those types must have a matching prevout and pass `verify_input`
+    /// - every input must have a standard witness (`check_witness_standard`)
+    ///
+    /// `empty` and `provably_unspendable` are left to `unknown_types`.
+    pub fn standard(reject_annex: bool, unknown_types: UnknownTypePolicy) -> VerifierRegistry {
+        let mut registry = VerifierRegistry::new(unknown_types);
+        let address_types: [(&str, AddressCheck); 5] = [
+            ("p2pkh", verify_p2pkh_address),
+            ("p2sh", verify_p2sh_address),
+            ("v0_p2wpkh", verify_v0_p2wpkh_address),
+            ("v0_p2wsh", verify_v0_p2wsh_address),
+            ("v1_p2tr", verify_v1_p2tr_address),
+        ];
+        for (script_type, verify_address) in address_types {
+            registry.register_output(script_type, move |transaction: &Transaction, index: usize| {
+                let vout = &transaction.vout[index];
+                match verify_address(&vout.scriptpubkey_address, &vout.scriptpubkey) {
+                    true => Ok(()),
+                    false => Err(ValidationError::OutputScript { index, script_type: vout.scriptpubkey_type.clone() }),
+                }
+            });
+            registry.register_input(script_type, move |transaction: &Transaction, index: usize| {
+                let prevout = &transaction.vin[index].prevout;
+                match verify_address(&prevout.scriptpubkey_address, &prevout.scriptpubkey) {
+                    true => Ok(()),
+                    false => Err(ValidationError::PrevoutScript { index, script_type: prevout.scriptpubkey_type.clone() }),
+                }
+            });
+            registry.register_input(script_type, verify_spend);
+        }
+
+        // Esplora labels bare multisig `unknown`, along with scripts no template matches
+        registry.register_output("unknown", |transaction: &Transaction, index: usize| {
+            match verify_multisig(&transaction.vout[index].scriptpubkey) {
+                true => Ok(()),
+                false => Err(ValidationError::OutputScript { index, script_type: "unknown".to_string() }),
+            }
+        });
+        registry.register_input("unknown", |transaction: &Transaction, index: usize| {
+            match verify_multisig(&transaction.vin[index].prevout.scriptpubkey) {
+                true => Ok(()),
+                false => Err(ValidationError::PrevoutScript { index, script_type: "unknown".to_string() }),
+            }
+        });
+        registry.register_input("unknown", verify_spend);
+
+        // The label already matches the script bytes (`label_mismatch`), so there is nothing more to check
+        registry.register_output("p2pk", |_: &Transaction, _: usize| Ok(()));
+        registry.register_output("op_return", |_: &Transaction, _: usize| Ok(()));
+        registry.register_input("p2pk", verify_spend);
+
+        // Valid but non-standard witnesses are not relayed, so not mined either
+        registry.register_input_check(move |transaction: &Transaction, index: usize| {
+            check_witness_standard(transaction, index, reject_annex)
+                .map_err(|error| ValidationError::NonStandardWitness { index, error })
+        });
+        registry
+    }
+
+    /// Adds a verifier for outputs of `script_type`.
+    pub fn register_output(&mut self, script_type: &str, verifier: impl OutputVerifier + 'static) {
+        self.outputs.entry(script_type.to_string()).or_default().push(Box::new(verifier));
+    }
+
+    /// Adds a verifier for inputs whose prevout is of `script_type`.
+    pub fn register_input(&mut self, script_type: &str, verifier: impl InputVerifier + 'static) {
+        self.inputs.entry(script_type.to_string()).or_default().push(Box::new(verifier));
+    }
+
+    /// Adds a check run on every input, before the verifiers for its prevout's type.
+    pub fn register_input_check(&mut self, verifier: impl InputVerifier + 'static) {
+        self.input_checks.push(Box::new(verifier));
+    }
+
+    /// The verifiers registered for outputs of `script_type`, if any.
+    pub fn output_verifiers(&self, script_type: &str) -> Option<&[Box<dyn OutputVerifier>]> {
+        self.outputs.get(script_type).map(Vec::as_slice)
+    }
+
+    /// The verifiers registered for inputs whose prevout is of `script_type`, if any.
+    pub fn input_verifiers(&self, script_type: &str) -> Option<&[Box<dyn InputVerifier>]> {
+        self.inputs.get(script_type).map(Vec::as_slice)
+    }
+
+    /// The checks run on every input.
+    pub fn input_checks(&self) -> &[Box<dyn InputVerifier>] {
+        &self.input_checks
+    }
+}
+
+// Runs the input's scripts through the interpreter
+fn verify_spend(transaction: &Transaction, index: usize) -> Result<(), ValidationError> {
+    verify_input(transaction, index).map_err(|error| ValidationError::Script { index, error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction_loader::load_json_transaction;
+    use crate::transaction_validator::validate_transaction;
+
+    #[test]
+    fn test_unknown_type_policy() {
+        // Three p2pkh inputs and a p2sh output
+        let mut transaction = load_json_transaction("00d7c8ddc2e75f6ba97520623390f01a910dc66a9e6a2052ee31f1b99aabdea5");
+        let accepting = VerifierRegistry::standard(true, UnknownTypePolicy::Accept);
+        let rejecting = VerifierRegistry::standard(true, UnknownTypePolicy::Reject);
+        assert_eq!(validate_transaction(&transaction, &rejecting), Ok(()));
+
+        // An output type nothing is registered for
+        let mut empty_output = transaction.clone();
+        empty_output.vout[0].scriptpubkey = String::new();
+        empty_output.vout[0].scriptpubkey_type = "empty".to_string();
+        assert_eq!(
+            validate_transaction(&empty_output, &rejecting),
+            Err(ValidationError::UnknownOutputType { index: 0, script_type: "empty".to_string() })
+        );
+        // Accepted, the output is not checked, but the inputs still are and now sign a different output
+        assert!(matches!(validate_transaction(&empty_output, &accepting), Err(ValidationError::Script { index: 0, .. })));
+
+        // An empty registry checks nothing unless unknown types are rejected
+        transaction.vout[0].value += 1;
+        let mut registry = VerifierRegistry::new(UnknownTypePolicy::Accept);
+        assert_eq!(validate_transaction(&transaction, &registry), Ok(()));
+        registry.unknown_types = UnknownTypePolicy::Reject;
+        registry.register_output("p2sh", |_: &Transaction, _: usize| Ok(()));
+        assert_eq!(
+            validate_transaction(&transaction, &registry),
+            Err(ValidationError::UnknownInputType { index: 0, script_type: "p2pkh".to_string() })
+        );
+    }
+
+    #[test]
+    fn test_op_return_output() {
+        // A p2wpkh spend with an OP_RETURN output, which has no address
+        let transaction = load_json_transaction("017993a44e612f4d034c63ee5b3586800498e267525ab935eab0675f3fbd3add");
+        let registry = VerifierRegistry::standard(true, UnknownTypePolicy::Reject);
+        assert_eq!(validate_transaction(&transaction, &registry), Ok(()));
+    }
+
+    #[test]
+    fn test_custom_verifiers() {
+        let mut transaction = load_json_transaction("00d7c8ddc2e75f6ba97520623390f01a910dc66a9e6a2052ee31f1b99aabdea5");
+
+        // A dust limit added on top of the standard p2sh verifier
+        let mut registry = VerifierRegistry::standard(true, UnknownTypePolicy::Reject);
+        registry.register_output("p2sh", |transaction: &Transaction, index: usize| match transaction.vout[index].value {
+            540.. => Ok(()),
+            _ => Err(ValidationError::OutputScript { index, script_type: "p2sh".to_string() }),
+        });
+        assert_eq!(validate_transaction(&transaction, &registry), Ok(()));
+
+        // Input checks run on every input, whatever its prevout type
+        registry.register_input_check(|transaction: &Transaction, index: usize| match transaction.vin[index].sequence {
+            0..=0xfffffffd => Ok(()),
+            _ => Err(ValidationError::PrevoutScript { index, script_type: "p2pkh".to_string() }),
+        });
+        assert_eq!(
+            validate_transaction(&transaction, &registry),
+            Err(ValidationError::PrevoutScript { index: 0, script_type: "p2pkh".to_string() })
+        );
+
+        // Outputs are checked first
+        transaction.vout[0].value = 539;
+        assert_eq!(
+            validate_transaction(&transaction, &registry),
+            Err(ValidationError::OutputScript { index: 0, script_type: "p2sh".to_string() })
+        );
+    }
+}